@@ -0,0 +1,712 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::instruction::Instruction;
+
+// Assembles the mnemonics the disassembler prints back into a ROM.
+//
+//   ; comments run to the end of the line
+//   SPEED  EQU 4                ; constants
+//   start: LD V0, SPEED         ; labels, on their own line or before code
+//          LD I, sprite + 1     ; expressions are terms joined with + and -
+//          JP start
+//   sprite: DB 0x3C, 0x42, %11111111
+//          DW 0x1234, start
+//          INCLUDE "font.asm"   ; relative to the including file
+//
+// Numbers are decimal, hex with 0x or $, or binary with 0b or %. Mnemonics,
+// registers and directives are case insensitive, labels are not.
+
+const ORIGIN: i64 = 0x200;
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.file.as_deref().unwrap_or("<input>");
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            file, self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone)]
+struct Location {
+    file: Option<String>,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, column: usize, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: self.file.clone(),
+            line: self.line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Str(String),
+    Punct(char),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    // 1-based, in characters
+    column: usize,
+}
+
+impl Token {
+    fn word(&self) -> Option<&str> {
+        match &self.kind {
+            TokenKind::Word(word) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.kind == TokenKind::Punct(c)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.word()
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+fn tokenize(text: &str, location: &Location) -> Result<Vec<Token>, AsmError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        let column = pos + 1;
+        if c == ';' {
+            break;
+        } else if c.is_whitespace() {
+            pos += 1;
+        } else if c == '"' {
+            let end = chars[pos + 1..]
+                .iter()
+                .position(|&c| c == '"')
+                .ok_or_else(|| location.error(column, "unterminated string"))?;
+            let text = chars[pos + 1..pos + 1 + end].iter().collect();
+            tokens.push(Token {
+                kind: TokenKind::Str(text),
+                column,
+            });
+            pos += end + 2;
+        } else if matches!(c, ',' | '[' | ']' | '-' | '+' | ':') {
+            tokens.push(Token {
+                kind: TokenKind::Punct(c),
+                column,
+            });
+            pos += 1;
+        } else if c.is_alphanumeric() || matches!(c, '_' | '.' | '$' | '%') {
+            let len = chars[pos..]
+                .iter()
+                .take_while(|&&c| c.is_alphanumeric() || matches!(c, '_' | '.' | '$' | '%'))
+                .count();
+            tokens.push(Token {
+                kind: TokenKind::Word(chars[pos..pos + len].iter().collect()),
+                column,
+            });
+            pos += len;
+        } else {
+            return Err(location.error(column, format!("unexpected character '{}'", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x").or(lower.strip_prefix('$')) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b").or(lower.strip_prefix('%')) {
+        i64::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+fn parse_register(token: &Token) -> Option<u8> {
+    let word = token.word()?;
+    let digit = word.strip_prefix(['V', 'v'])?;
+    match u8::from_str_radix(digit, 16) {
+        Ok(n) if digit.len() == 1 => Some(n),
+        _ => None,
+    }
+}
+
+// Terms joined by + and -, evaluated once every label is known
+#[derive(Debug, Clone)]
+struct Expr {
+    tokens: Vec<Token>,
+    // Where errors point when there are no tokens: the keyword, mnemonic
+    // or operand the expression belongs to
+    column: usize,
+}
+
+impl Expr {
+    fn new(tokens: &[Token], column: usize) -> Self {
+        Expr {
+            tokens: tokens.to_vec(),
+            column,
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .first()
+            .map_or(self.column, |token| token.column)
+    }
+
+    fn eval(&self, symbols: &HashMap<String, i64>, location: &Location) -> Result<i64, AsmError> {
+        let mut total = 0;
+        let mut sign = 1;
+        let mut expect_term = true;
+        for token in &self.tokens {
+            match &token.kind {
+                TokenKind::Punct(c @ ('+' | '-')) if expect_term => {
+                    if *c == '-' {
+                        sign = -sign;
+                    }
+                }
+                TokenKind::Punct(c @ ('+' | '-')) => {
+                    sign = if *c == '-' { -1 } else { 1 };
+                    expect_term = true;
+                }
+                TokenKind::Word(word) if expect_term => {
+                    let starts_numeric =
+                        word.starts_with(|c: char| c.is_ascii_digit() || c == '$' || c == '%');
+                    let value = if starts_numeric {
+                        parse_number(word).ok_or_else(|| {
+                            location.error(token.column, format!("invalid number '{}'", word))
+                        })?
+                    } else {
+                        *symbols.get(word.as_str()).ok_or_else(|| {
+                            location.error(token.column, format!("unknown symbol '{}'", word))
+                        })?
+                    };
+                    total += sign * value;
+                    sign = 1;
+                    expect_term = false;
+                }
+                _ => return Err(location.error(token.column, "expected + or - between terms")),
+            }
+        }
+        if expect_term {
+            let column = self.tokens.last().map_or(self.column, |token| token.column);
+            return Err(location.error(column, "expression is missing a value"));
+        }
+        Ok(total)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Register(u8),
+    // I, DT, ST, K, F, HF, B and R. LONG and EQU are only reserved names.
+    Keyword(&'static str),
+    // [I]
+    IndirectI,
+    // LONG nnnn
+    Long(Expr),
+    // Vx - Vy
+    Range(u8, u8),
+    Value(Expr),
+}
+
+const KEYWORDS: [&str; 10] = ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG", "EQU"];
+
+fn parse_operand(
+    tokens: &[Token],
+    location: &Location,
+    column: usize,
+) -> Result<Operand, AsmError> {
+    match tokens {
+        [] => Err(location.error(column, "missing operand")),
+        [token] if parse_register(token).is_some() => {
+            Ok(Operand::Register(parse_register(token).unwrap()))
+        }
+        [long, rest @ ..] if long.is_keyword("LONG") => {
+            Ok(Operand::Long(Expr::new(rest, long.column)))
+        }
+        [token] if KEYWORDS.iter().any(|k| token.is_keyword(k)) => {
+            let keyword = KEYWORDS.iter().find(|k| token.is_keyword(k)).unwrap();
+            Ok(Operand::Keyword(keyword))
+        }
+        [open, i, close] if open.is_punct('[') && i.is_keyword("I") && close.is_punct(']') => {
+            Ok(Operand::IndirectI)
+        }
+        [x, dash, y]
+            if dash.is_punct('-') && parse_register(x).is_some() && parse_register(y).is_some() =>
+        {
+            Ok(Operand::Range(
+                parse_register(x).unwrap(),
+                parse_register(y).unwrap(),
+            ))
+        }
+        _ => Ok(Operand::Value(Expr::new(tokens, column))),
+    }
+}
+
+// Splits on top level commas
+fn split_operands(tokens: &[Token]) -> Vec<&[Token]> {
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+    tokens.split(|token| token.is_punct(',')).collect()
+}
+
+#[derive(Debug)]
+enum StatementKind {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+}
+
+#[derive(Debug)]
+struct Statement {
+    location: Location,
+    column: usize,
+    kind: StatementKind,
+}
+
+#[derive(Default)]
+struct Assembler {
+    statements: Vec<Statement>,
+    symbols: HashMap<String, i64>,
+    address: i64,
+}
+
+// Assembles source text. INCLUDE paths are relative to the working directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler {
+        address: ORIGIN,
+        ..Assembler::default()
+    };
+    assembler.read(source, None, 0)?;
+    assembler.emit()
+}
+
+// Assembles a file. INCLUDE paths are relative to the file doing the including.
+pub fn assemble_file(path: &Path) -> Result<Vec<u8>, AsmError> {
+    let source = std::fs::read_to_string(path).map_err(|err| AsmError {
+        file: Some(path.display().to_string()),
+        line: 0,
+        column: 0,
+        message: err.to_string(),
+    })?;
+    let mut assembler = Assembler {
+        address: ORIGIN,
+        ..Assembler::default()
+    };
+    assembler.read(&source, Some(path), 0)?;
+    assembler.emit()
+}
+
+impl Assembler {
+    // First pass: collect statements, give every label its address
+    fn read(&mut self, source: &str, file: Option<&Path>, depth: usize) -> Result<(), AsmError> {
+        for (n, text) in source.lines().enumerate() {
+            let location = Location {
+                file: file.map(|path| path.display().to_string()),
+                line: n + 1,
+            };
+            let mut tokens = tokenize(text, &location)?;
+
+            // NAME EQU value
+            if tokens.len() >= 2 && tokens[1].is_keyword("EQU") {
+                let name = tokens[0]
+                    .word()
+                    .ok_or_else(|| location.error(tokens[0].column, "expected a constant name"))?;
+                let expr = Expr::new(&tokens[2..], tokens[1].column);
+                let value = expr.eval(&self.symbols, &location)?;
+                self.define(name, value, &location, tokens[0].column)?;
+                continue;
+            }
+
+            // label:
+            if tokens.len() >= 2 && tokens[1].is_punct(':') {
+                let name = tokens[0]
+                    .word()
+                    .ok_or_else(|| location.error(tokens[0].column, "expected a label name"))?;
+                self.define(name, self.address, &location, tokens[0].column)?;
+                tokens.drain(..2);
+            }
+
+            let Some((first, rest)) = tokens.split_first() else {
+                continue;
+            };
+            let column = first.column;
+            let mnemonic = first
+                .word()
+                .ok_or_else(|| location.error(column, "expected an instruction"))?
+                .to_ascii_uppercase();
+
+            let kind = match mnemonic.as_str() {
+                "INCLUDE" => {
+                    let [Token {
+                        kind: TokenKind::Str(name),
+                        ..
+                    }] = rest
+                    else {
+                        return Err(location.error(column, "INCLUDE needs a quoted file name"));
+                    };
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(location.error(column, "includes are nested too deeply"));
+                    }
+                    let path = match file.and_then(Path::parent) {
+                        Some(dir) => dir.join(name),
+                        None => PathBuf::from(name),
+                    };
+                    let source = std::fs::read_to_string(&path).map_err(|err| {
+                        location.error(column, format!("can't include '{}': {}", name, err))
+                    })?;
+                    self.read(&source, Some(&path), depth + 1)?;
+                    continue;
+                }
+                "DB" | "DW" => {
+                    let values: Vec<Expr> = split_operands(rest)
+                        .into_iter()
+                        .map(|tokens| Expr::new(tokens, column))
+                        .collect();
+                    if values.is_empty() {
+                        return Err(location.error(column, format!("{} needs a value", mnemonic)));
+                    }
+                    if mnemonic == "DB" {
+                        self.address += values.len() as i64;
+                        StatementKind::Bytes(values)
+                    } else {
+                        self.address += 2 * values.len() as i64;
+                        StatementKind::Words(values)
+                    }
+                }
+                _ => {
+                    let mut operands = Vec::new();
+                    for tokens in split_operands(rest) {
+                        let column = tokens.first().map_or(column, |token| token.column);
+                        operands.push(parse_operand(tokens, &location, column)?);
+                    }
+                    let long = operands.iter().any(|o| matches!(o, Operand::Long(_)));
+                    self.address += if long { 4 } else { 2 };
+                    StatementKind::Instruction { mnemonic, operands }
+                }
+            };
+            self.statements.push(Statement {
+                location,
+                column,
+                kind,
+            });
+        }
+        Ok(())
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        value: i64,
+        location: &Location,
+        column: usize,
+    ) -> Result<(), AsmError> {
+        // Operands read these as registers, keywords or numbers before
+        // looking for symbols, so a symbol with such a name couldn't be used
+        let token = Token {
+            kind: TokenKind::Word(name.to_string()),
+            column,
+        };
+        if parse_register(&token).is_some()
+            || KEYWORDS.iter().any(|k| token.is_keyword(k))
+            || parse_number(name).is_some()
+        {
+            return Err(location.error(column, format!("'{}' can't be used as a name", name)));
+        }
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(location.error(column, format!("'{}' is already defined", name)));
+        }
+        Ok(())
+    }
+
+    // Second pass: everything is known, produce the bytes
+    fn emit(&self) -> Result<Vec<u8>, AsmError> {
+        let mut out = Vec::new();
+        for statement in &self.statements {
+            let location = &statement.location;
+            let value = |expr: &Expr, min: i64, max: i64| -> Result<i64, AsmError> {
+                let value = expr.eval(&self.symbols, location)?;
+                if !(min..=max).contains(&value) {
+                    return Err(location.error(
+                        expr.column(),
+                        format!("value {} out of range {}..={}", value, min, max),
+                    ));
+                }
+                Ok(value)
+            };
+            match &statement.kind {
+                StatementKind::Bytes(values) => {
+                    for expr in values {
+                        out.push(value(expr, -128, 0xFF)? as u8);
+                    }
+                }
+                StatementKind::Words(values) => {
+                    for expr in values {
+                        out.extend_from_slice(
+                            &(value(expr, -0x8000, 0xFFFF)? as u16).to_be_bytes(),
+                        );
+                    }
+                }
+                StatementKind::Instruction { mnemonic, operands } => {
+                    let byte = |expr: &Expr| value(expr, -128, 0xFF).map(|v| v as u8);
+                    let nibble = |expr: &Expr| value(expr, 0, 0xF).map(|v| v as u8);
+                    let addr = |expr: &Expr| value(expr, 0, 0xFFF).map(|v| v as u16);
+                    let instruction =
+                        select(mnemonic, operands, byte, nibble, addr).ok_or_else(|| {
+                            let message = if MNEMONICS.contains(&mnemonic.as_str()) {
+                                format!("invalid operands for {}", mnemonic)
+                            } else {
+                                format!("unknown instruction '{}'", mnemonic)
+                            };
+                            location.error(statement.column, message)
+                        })??;
+                    out.extend_from_slice(&instruction.encode().to_be_bytes());
+                    if let Some(Operand::Long(expr)) = operands.last() {
+                        out.extend_from_slice(&(value(expr, 0, 0xFFFF)? as u16).to_be_bytes());
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+const MNEMONICS: [&str; 31] = [
+    "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE",
+    "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW",
+    "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+// Picks the instruction for a mnemonic and its operand shapes. None means
+// no form of the mnemonic takes these operands.
+fn select(
+    mnemonic: &str,
+    operands: &[Operand],
+    byte: impl Fn(&Expr) -> Result<u8, AsmError>,
+    nibble: impl Fn(&Expr) -> Result<u8, AsmError>,
+    addr: impl Fn(&Expr) -> Result<u16, AsmError>,
+) -> Option<Result<Instruction, AsmError>> {
+    use Instruction::*;
+    use Operand::{IndirectI, Keyword, Long, Range, Register as V, Value};
+
+    let instruction = match (mnemonic, operands) {
+        ("CLS", []) => Ok(Clear),
+        ("RET", []) => Ok(Return),
+        ("SCD", [Value(n)]) => nibble(n).map(ScrollDown),
+        ("SCU", [Value(n)]) => nibble(n).map(ScrollUp),
+        ("SCR", []) => Ok(ScrollRight),
+        ("SCL", []) => Ok(ScrollLeft),
+        ("EXIT", []) => Ok(Exit),
+        ("LOW", []) => Ok(LowRes),
+        ("HIGH", []) => Ok(HighRes),
+        ("JP", [Value(nnn)]) => addr(nnn).map(Jump),
+        ("JP", [V(0), Value(nnn)]) => addr(nnn).map(JumpOffset),
+        ("CALL", [Value(nnn)]) => addr(nnn).map(Call),
+        ("SE", [V(x), V(y)]) => Ok(SkipEqual { x: *x, y: *y }),
+        ("SE", [V(x), Value(kk)]) => byte(kk).map(|kk| SkipEqualByte { x: *x, kk }),
+        ("SNE", [V(x), V(y)]) => Ok(SkipNotEqual { x: *x, y: *y }),
+        ("SNE", [V(x), Value(kk)]) => byte(kk).map(|kk| SkipNotEqualByte { x: *x, kk }),
+        ("SAVE", [Range(x, y)]) => Ok(SaveRange { x: *x, y: *y }),
+        ("LOAD", [Range(x, y)]) => Ok(LoadRange { x: *x, y: *y }),
+        ("LD", [V(x), V(y)]) => Ok(Load { x: *x, y: *y }),
+        ("LD", [V(x), Value(kk)]) => byte(kk).map(|kk| LoadByte { x: *x, kk }),
+        ("LD", [Keyword("I"), Value(nnn)]) => addr(nnn).map(LoadI),
+        ("LD", [Keyword("I"), Long(_)]) => Ok(LoadLongI),
+        ("LD", [V(x), Keyword("DT")]) => Ok(LoadDelay(*x)),
+        ("LD", [V(x), Keyword("K")]) => Ok(WaitKey(*x)),
+        ("LD", [Keyword("DT"), V(x)]) => Ok(SetDelay(*x)),
+        ("LD", [Keyword("ST"), V(x)]) => Ok(SetSound(*x)),
+        ("LD", [Keyword("F"), V(x)]) => Ok(LoadFont(*x)),
+        ("LD", [Keyword("HF"), V(x)]) => Ok(LoadBigFont(*x)),
+        ("LD", [Keyword("B"), V(x)]) => Ok(StoreBcd(*x)),
+        ("LD", [IndirectI, V(x)]) => Ok(StoreRegisters(*x)),
+        ("LD", [V(x), IndirectI]) => Ok(LoadRegisters(*x)),
+        ("LD", [Keyword("R"), V(x)]) => Ok(StoreFlags(*x)),
+        ("LD", [V(x), Keyword("R")]) => Ok(LoadFlags(*x)),
+        ("ADD", [V(x), V(y)]) => Ok(Add { x: *x, y: *y }),
+        ("ADD", [V(x), Value(kk)]) => byte(kk).map(|kk| AddByte { x: *x, kk }),
+        ("ADD", [Keyword("I"), V(x)]) => Ok(AddI(*x)),
+        ("OR", [V(x), V(y)]) => Ok(Or { x: *x, y: *y }),
+        ("AND", [V(x), V(y)]) => Ok(And { x: *x, y: *y }),
+        ("XOR", [V(x), V(y)]) => Ok(Xor { x: *x, y: *y }),
+        ("SUB", [V(x), V(y)]) => Ok(Sub { x: *x, y: *y }),
+        ("SHR", [V(x), V(y)]) => Ok(ShiftRight { x: *x, y: *y }),
+        ("SUBN", [V(x), V(y)]) => Ok(SubNegated { x: *x, y: *y }),
+        ("SHL", [V(x), V(y)]) => Ok(ShiftLeft { x: *x, y: *y }),
+        ("RND", [V(x), Value(kk)]) => byte(kk).map(|kk| Random { x: *x, kk }),
+        ("DRW", [V(x), V(y), Value(n)]) => nibble(n).map(|n| Draw { x: *x, y: *y, n }),
+        ("SKP", [V(x)]) => Ok(SkipKeyPressed(*x)),
+        ("SKNP", [V(x)]) => Ok(SkipKeyNotPressed(*x)),
+        ("PLANE", [Value(n)]) => nibble(n).map(Plane),
+        ("AUDIO", []) => Ok(Audio),
+        ("PITCH", [V(x)]) => Ok(Pitch(*x)),
+        _ => return None,
+    };
+    Some(instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::disassemble;
+
+    fn error_at(source: &str) -> (usize, usize, String) {
+        let err = assemble(source).unwrap_err();
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn test_assemble_labels_constants_and_data() {
+        let source = "\
+; draw a sprite forever
+HEIGHT  EQU 3
+start:  LD V0, 0x0C
+        LD I, sprite
+        DRW V0, V1, HEIGHT
+loop:   JP loop
+sprite: DB 0x3C, $42, %11111111
+        DW start + 2
+";
+        assert_eq!(
+            assemble(source),
+            Ok(vec![
+                0x60, 0x0C, 0xA2, 0x08, 0xD0, 0x13, 0x12, 0x06, 0x3C, 0x42, 0xFF, 0x02, 0x02,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_assemble_schip_and_xochip() {
+        let source =
+            "HIGH\nSCD 4\nLD HF, V2\nLD R, V3\nPLANE 3\nSAVE V1 - V4\nLD I, LONG 0x1234\nAUDIO";
+        assert_eq!(
+            assemble(source),
+            Ok(vec![
+                0x00, 0xFF, 0x00, 0xC4, 0xF2, 0x30, 0xF3, 0x75, 0xF3, 0x01, 0x51, 0x42, 0xF0, 0x00,
+                0x12, 0x34, 0xF0, 0x02,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors_give_line_and_column() {
+        assert_eq!(
+            error_at("CLS\n  LD V0, missing"),
+            (2, 10, "unknown symbol 'missing'".to_string())
+        );
+        assert_eq!(
+            error_at("  FOO V1"),
+            (1, 3, "unknown instruction 'FOO'".to_string())
+        );
+        assert_eq!(
+            error_at("JP V1, 0x200"),
+            (1, 1, "invalid operands for JP".to_string())
+        );
+        assert_eq!(
+            error_at("DRW V0, V1, 16"),
+            (1, 13, "value 16 out of range 0..=15".to_string())
+        );
+        assert_eq!(
+            error_at("a: CLS\na: RET"),
+            (2, 1, "'a' is already defined".to_string())
+        );
+    }
+
+    #[test]
+    fn test_keywords_and_registers_are_not_names() {
+        assert_eq!(
+            error_at("start:\n JP f\nf:\n CLS"),
+            (3, 1, "'f' can't be used as a name".to_string())
+        );
+        assert_eq!(
+            error_at("i EQU 5\n LD V0, i"),
+            (1, 1, "'i' can't be used as a name".to_string())
+        );
+        assert_eq!(
+            error_at("v3: CLS"),
+            (1, 1, "'v3' can't be used as a name".to_string())
+        );
+        assert_eq!(
+            error_at("long: CLS\n JP long"),
+            (1, 1, "'long' can't be used as a name".to_string())
+        );
+        assert_eq!(
+            error_at("equ: CLS\n JP equ"),
+            (1, 1, "'equ' can't be used as a name".to_string())
+        );
+    }
+
+    #[test]
+    fn test_empty_expressions_point_at_their_keyword() {
+        assert_eq!(
+            error_at("CLS\nLD I, LONG"),
+            (2, 7, "expression is missing a value".to_string())
+        );
+        assert_eq!(
+            error_at("SPEED EQU"),
+            (1, 7, "expression is missing a value".to_string())
+        );
+    }
+
+    #[test]
+    fn test_include_is_relative_to_the_including_file() {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("main.asm"), "CALL draw\nINCLUDE \"lib/draw.asm\"").unwrap();
+        std::fs::write(dir.join("lib/draw.asm"), "draw: CLS\n  RET\n  JP nowhere").unwrap();
+
+        let err = assemble_file(&dir.join("main.asm")).unwrap_err();
+        assert_eq!((err.line, err.column), (3, 6));
+        assert!(err.file.unwrap().ends_with("draw.asm"));
+
+        std::fs::write(dir.join("lib/draw.asm"), "draw: CLS\n  RET").unwrap();
+        let rom = assemble_file(&dir.join("main.asm"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(rom, Ok(vec![0x22, 0x02, 0x00, 0xE0, 0x00, 0xEE]));
+    }
+
+    #[test]
+    fn test_disassemble_assemble_round_trip() {
+        // Every opcode, including the ones nothing decodes, then a long load
+        // and a stray byte
+        let mut rom: Vec<u8> = (0..=u16::MAX).flat_map(u16::to_be_bytes).collect();
+        rom.extend_from_slice(&[0xF0, 0x00, 0xAB, 0xCD, 0x42]);
+        for xo_chip in [false, true] {
+            let source: Vec<String> = disassemble(&rom, 0x200, xo_chip)
+                .iter()
+                .map(|line| line.mnemonic())
+                .collect();
+            assert_eq!(assemble(&source.join("\n")).unwrap(), rom);
+        }
+    }
+}
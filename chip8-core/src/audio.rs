@@ -0,0 +1,191 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chip::AUDIO_PATTERN_SIZE;
+
+// XO-CHIP plays its pattern at 4000 * 2^((pitch - 64) / 48) bits per second
+const XO_CHIP_BASE_RATE: f32 = 4000.0;
+const PATTERN_BITS: usize = AUDIO_PATTERN_SIZE * 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            _ => Err(format!(
+                "unknown waveform '{}', expected one of: square, sine, triangle",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Waveform::Square => write!(f, "square"),
+            Waveform::Sine => write!(f, "sine"),
+            Waveform::Triangle => write!(f, "triangle"),
+        }
+    }
+}
+
+// Generates the beeper signal as f32 samples. It knows nothing about sound
+// devices: the frontend tells it whether the sound timer is running and
+// pulls samples from it.
+pub struct ToneGenerator {
+    sample_rate: f32,
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub volume: f32,
+    playing: bool,
+    // XO-CHIP pattern and its playback pitch, replacing the plain tone
+    pattern: Option<([u8; AUDIO_PATTERN_SIZE], u8)>,
+    // Position in the current period (or in the pattern), from 0 to 1
+    phase: f32,
+}
+
+impl ToneGenerator {
+    pub fn new(sample_rate: u32, waveform: Waveform, frequency: f32, volume: f32) -> Self {
+        ToneGenerator {
+            sample_rate: sample_rate as f32,
+            waveform,
+            frequency,
+            volume,
+            playing: false,
+            pattern: None,
+            phase: 0.0,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn set_playing(&mut self, playing: bool) {
+        if playing && !self.playing {
+            self.phase = 0.0;
+        }
+        self.playing = playing;
+    }
+
+    pub fn set_pattern(&mut self, pattern: Option<([u8; AUDIO_PATTERN_SIZE], u8)>) {
+        self.pattern = pattern;
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = self.next_sample();
+        }
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        if !self.playing {
+            return 0.0;
+        }
+
+        let (value, step) = match self.pattern {
+            Some((pattern, pitch)) => {
+                let bit = (self.phase * PATTERN_BITS as f32) as usize % PATTERN_BITS;
+                let set = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                let rate = XO_CHIP_BASE_RATE * 2f32.powf((pitch as f32 - 64.0) / 48.0);
+                let value = if set { 1.0 } else { -1.0 };
+                (value, rate / PATTERN_BITS as f32 / self.sample_rate)
+            }
+            None => {
+                let value = match self.waveform {
+                    Waveform::Square => {
+                        if self.phase < 0.5 {
+                            1.0
+                        } else {
+                            -1.0
+                        }
+                    }
+                    Waveform::Sine => (self.phase * 2.0 * PI).sin(),
+                    Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+                };
+                (value, self.frequency / self.sample_rate)
+            }
+        };
+
+        self.phase = (self.phase + step).fract();
+        value * self.volume
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silent_when_not_playing() {
+        let mut tone = ToneGenerator::new(44100, Waveform::Square, 440.0, 0.5);
+        let mut out = [1.0; 64];
+        tone.fill(&mut out);
+        assert!(out.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_square_wave_period_and_volume() {
+        // 8 samples per period
+        let mut tone = ToneGenerator::new(800, Waveform::Square, 100.0, 0.25);
+        tone.set_playing(true);
+        let mut out = [0.0; 16];
+        tone.fill(&mut out);
+        assert_eq!(
+            out[..8],
+            [0.25, 0.25, 0.25, 0.25, -0.25, -0.25, -0.25, -0.25]
+        );
+        assert_eq!(out[..8], out[8..]);
+    }
+
+    #[test]
+    fn test_triangle_and_sine_stay_in_range() {
+        for waveform in [Waveform::Sine, Waveform::Triangle] {
+            let mut tone = ToneGenerator::new(44100, waveform, 440.0, 1.0);
+            tone.set_playing(true);
+            let mut out = [0.0; 1000];
+            tone.fill(&mut out);
+            assert!(out.iter().all(|s| (-1.0..=1.0).contains(s)));
+            assert!(out.iter().any(|&s| s > 0.9) && out.iter().any(|&s| s < -0.9));
+        }
+    }
+
+    #[test]
+    fn test_xochip_pattern_playback() {
+        // At pitch 64 the pattern plays at 4000 bits per second, one bit per sample here
+        let mut tone = ToneGenerator::new(4000, Waveform::Square, 440.0, 1.0);
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        pattern[0] = 0b1010_0000;
+        tone.set_pattern(Some((pattern, 64)));
+        tone.set_playing(true);
+        let mut out = [0.0; 4];
+        tone.fill(&mut out);
+        assert_eq!(out, [1.0, -1.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_default_pattern_is_a_square_wave() {
+        // Without F002 a sound timer beep still has to be audible
+        let chip8 = crate::Chip8::new();
+        let mut tone = ToneGenerator::new(4000, Waveform::Square, 440.0, 1.0);
+        tone.set_pattern(Some((chip8.audio_pattern, chip8.pitch)));
+        tone.set_playing(true);
+        let mut out = [0.0; 16];
+        tone.fill(&mut out);
+        assert_eq!(out[..8], [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]);
+        assert_eq!(out[..8], out[8..]);
+    }
+}
@@ -0,0 +1,63 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use chip8_core::assembler::assemble_file;
+
+const USAGE: &str = "usage: chip8-asm [options] <source>
+
+options:
+  -o <file>          where to write the ROM, the source name with .ch8 by default";
+
+struct Args {
+    source: PathBuf,
+    output: PathBuf,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut source = None;
+    let mut output = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" => {
+                let path = iter.next().ok_or("-o needs a file name")?;
+                output = Some(PathBuf::from(path));
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ if source.is_none() => source = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let source = source.ok_or("You must provide a file to assemble")?;
+    let output = output.unwrap_or_else(|| Path::new(&source).with_extension("ch8"));
+    Ok(Args { source, output })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let rom = match assemble_file(&args.source) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = std::fs::write(&args.output, &rom) {
+        eprintln!("Error writing ROM: {}", err);
+        process::exit(1);
+    }
+    println!("Wrote {} bytes to {}", rom.len(), args.output.display());
+}
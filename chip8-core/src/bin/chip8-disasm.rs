@@ -0,0 +1,93 @@
+use std::env;
+use std::process;
+
+use chip8_core::instruction::{disassemble, Disassembly};
+use chip8_core::Mode;
+
+const USAGE: &str = "usage: chip8-disasm [options] <rom>
+
+options:
+  --octo             print Octo syntax instead of Cowgod-style mnemonics
+  --mode <mode>      chip8 or xochip, which decodes F000 NNNN as one instruction";
+
+// Programs are loaded here, so that's where the listing addresses start
+const LOAD_ADDRESS: u16 = 0x200;
+
+struct Args {
+    rom: String,
+    octo: bool,
+    mode: Mode,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut rom = None;
+    let mut octo = false;
+    let mut mode = Mode::Chip8;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--octo" => octo = true,
+            "--mode" => {
+                let name = iter.next().ok_or("--mode needs a mode name")?;
+                mode = name.parse()?;
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            _ if rom.is_none() => rom = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let rom = rom.ok_or("You must provide a file to disassemble")?;
+    Ok(Args { rom, octo, mode })
+}
+
+// Raw bytes grouped into words, as they'd be written in a hex dump
+fn hex_bytes(line: &Disassembly) -> String {
+    let words: Vec<String> = line
+        .bytes
+        .chunks(2)
+        .map(|word| word.iter().map(|byte| format!("{:02X}", byte)).collect())
+        .collect();
+    words.join(" ")
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let program = match std::fs::read(&args.rom) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("Error reading ROM: {}", err);
+            process::exit(1);
+        }
+    };
+
+    let listing = disassemble(&program, LOAD_ADDRESS, args.mode == Mode::XoChip);
+    for line in &listing {
+        // Addresses and bytes go in a comment, so the output still assembles
+        if args.octo {
+            println!(
+                "{:<28}# {:03X}  {}",
+                line.octo(),
+                line.addr,
+                hex_bytes(line)
+            );
+        } else {
+            println!(
+                "{:<28}; {:03X}  {}",
+                line.mnemonic(),
+                line.addr,
+                hex_bytes(line)
+            );
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chip8_core::headless::{state_json, Finish, Headless, KeyScript, StopCondition};
+use chip8_core::octo::read_rom;
+use chip8_core::png::screenshot;
+use chip8_core::random::{parse_seed, RngKind};
+use chip8_core::video::{VideoFormat, VideoRecorder};
+use chip8_core::{Chip8, Mode, Movie, Palette, Quirks, Theme};
+
+const USAGE: &str = "usage: chip8-headless [options] <rom>
+
+options:
+  --frames <n>          60 Hz frames to run (default 600, or the movie's length)
+  --ipf <n>             instructions executed per frame (default 11)
+  --quirks <preset>     vip, chip48, schip or xochip
+  --mode <mode>         chip8 or xochip
+  --seed <n>            random number seed (default 0, so runs repeat)
+  --rng <kind>          splitmix, the only generator for now
+  --until <condition>   stop early at pc=<addr> or opcode=<opcode>, repeatable
+  --keys <script>       key presses as FRAME:KEY[:FRAMES], e.g. \"60:5 90:A:10\"
+  --keys-file <file>    read the key script from a file
+  --movie <file>        replay a recorded movie, its quirks, mode, seed and
+                        speed replace the options above
+  --ascii               print the final screen as text
+  --png <file>          write the final screen as a PNG
+  --scale <n>           draw each pixel of the PNG or video as an n x n block
+                        (default 1)
+  --video <file>        record every frame as a GIF, Y4M or raw rgb24 video,
+                        by extension, - for stdout
+  --video-format <fmt>  gif, y4m or rgb, when the extension doesn't say
+  --theme <name>        PNG and video colors: default, green, amber, lcd or
+                        octo
+  --json <file>         write registers and memory as JSON, - for stdout
+
+exit status:
+  0  the run finished: all frames ran, a stop condition was reached or the
+     program exited
+  1  bad arguments or ROM
+  2  the emulator faulted
+  3  --until was given but no condition was reached";
+
+const DEFAULT_FRAMES: u64 = 600;
+// Same as the emulator's default speed
+const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 11;
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_FAULT: i32 = 2;
+const EXIT_NOT_REACHED: i32 = 3;
+
+struct Args {
+    rom: String,
+    frames: Option<u64>,
+    instructions_per_frame: u32,
+    quirks: Quirks,
+    mode: Mode,
+    rng: RngKind,
+    seed: u64,
+    stop_at: Vec<StopCondition>,
+    keys: KeyScript,
+    movie: Option<String>,
+    ascii: bool,
+    png: Option<String>,
+    scale: u32,
+    video: Option<(String, VideoFormat)>,
+    theme: Theme,
+    json: Option<String>,
+}
+
+fn parse_positive(option: &str, value: Option<&String>) -> Result<u64, String> {
+    let value = value.ok_or(format!("{} needs a number", option))?;
+    match value.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "{} needs a positive number, got '{}'",
+            option, value
+        )),
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut rom = None;
+    let mut frames = None;
+    let mut instructions_per_frame = DEFAULT_INSTRUCTIONS_PER_FRAME;
+    let mut quirks = Quirks::default();
+    let mut mode = None;
+    let mut rng = RngKind::default();
+    let mut seed = 0;
+    let mut stop_at = Vec::new();
+    let mut keys = KeyScript::default();
+    let mut movie = None;
+    let mut ascii = false;
+    let mut png = None;
+    let mut scale = 1;
+    let mut video = None;
+    let mut video_format = None;
+    let mut theme = Theme::default();
+    let mut json = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--frames" => frames = Some(parse_positive(arg, iter.next())?),
+            "--ipf" => {
+                instructions_per_frame = parse_positive(arg, iter.next())?
+                    .try_into()
+                    .map_err(|_| "--ipf is too large")?;
+            }
+            "--quirks" => {
+                let name = iter.next().ok_or("--quirks needs a preset name")?;
+                quirks = name.parse()?;
+            }
+            "--mode" => {
+                let name = iter.next().ok_or("--mode needs a mode name")?;
+                mode = Some(name.parse()?);
+            }
+            "--seed" => seed = parse_seed(iter.next().ok_or("--seed needs a number")?)?,
+            "--rng" => {
+                let name = iter.next().ok_or("--rng needs a generator name")?;
+                rng = name.parse()?;
+            }
+            "--until" => {
+                let condition = iter.next().ok_or("--until needs a condition")?;
+                stop_at.push(condition.parse()?);
+            }
+            "--keys" => {
+                let script = iter.next().ok_or("--keys needs a key script")?;
+                keys = script.parse()?;
+            }
+            "--keys-file" => {
+                let path = iter.next().ok_or("--keys-file needs a file name")?;
+                let script = std::fs::read_to_string(path)
+                    .map_err(|err| format!("Error reading {}: {}", path, err))?;
+                keys = script.parse()?;
+            }
+            "--movie" => movie = Some(iter.next().ok_or("--movie needs a file name")?.clone()),
+            "--ascii" => ascii = true,
+            "--png" => png = Some(iter.next().ok_or("--png needs a file name")?.clone()),
+            "--scale" => {
+                scale = parse_positive(arg, iter.next())?
+                    .try_into()
+                    .map_err(|_| "--scale is too large")?;
+            }
+            "--video" => video = Some(iter.next().ok_or("--video needs a file name")?.clone()),
+            "--video-format" => {
+                let name = iter.next().ok_or("--video-format needs a format")?;
+                video_format = Some(name.parse()?);
+            }
+            "--theme" => {
+                let name = iter.next().ok_or("--theme needs a theme name")?;
+                theme = name.parse()?;
+            }
+            "--json" => json = Some(iter.next().ok_or("--json needs a file name")?.clone()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            _ if rom.is_none() => rom = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let rom = rom.ok_or("You must provide a file to be loaded")?;
+    let video = match video {
+        Some(path) => {
+            let format = video_format
+                .or_else(|| VideoFormat::from_path(&path))
+                .ok_or(format!(
+                    "can't tell the video format of '{}', use --video-format",
+                    path
+                ))?;
+            Some((path, format))
+        }
+        None => None,
+    };
+    // Like the emulator, the xochip preset implies the XO-CHIP machine
+    let mode = mode.unwrap_or(if quirks == Quirks::xochip() {
+        Mode::XoChip
+    } else {
+        Mode::Chip8
+    });
+    Ok(Args {
+        rom,
+        frames,
+        instructions_per_frame,
+        quirks,
+        mode,
+        rng,
+        seed,
+        stop_at,
+        keys,
+        movie,
+        ascii,
+        png,
+        scale,
+        video,
+        theme,
+        json,
+    })
+}
+
+fn write_output(path: &str, data: &[u8]) -> Result<(), String> {
+    std::fs::write(path, data).map_err(|err| format!("Error writing {}: {}", path, err))
+}
+
+fn dump(args: &Args, chip8: &Chip8) -> Result<(), String> {
+    if args.ascii {
+        print!("{}", chip8.screen.to_ascii());
+    }
+    if let Some(path) = &args.png {
+        write_output(
+            path,
+            &screenshot(&chip8.screen, &args.theme.colors(), args.scale),
+        )?;
+    }
+    match args.json.as_deref() {
+        Some("-") => print!("{}", state_json(chip8)),
+        Some(path) => write_output(path, state_json(chip8).as_bytes())?,
+        None => {}
+    }
+    Ok(())
+}
+
+// Runs like `Headless::run`, writing every frame to a video
+fn record(
+    headless: &mut Headless,
+    frames: u64,
+    path: &str,
+    format: VideoFormat,
+    colors: &Palette,
+    scale: u32,
+) -> Result<Finish, String> {
+    let out: Box<dyn Write> = if path == "-" {
+        Box::new(io::stdout().lock())
+    } else {
+        let file = File::create(path).map_err(|err| format!("Error writing {}: {}", path, err))?;
+        Box::new(file)
+    };
+    let error = |err: io::Error| format!("Error writing {}: {}", path, err);
+    let mut recorder =
+        VideoRecorder::new(BufWriter::new(out), format, colors, scale).map_err(error)?;
+    let mut result = Ok(());
+    let finish = headless.run_with(frames, |chip8| {
+        if result.is_ok() {
+            result = recorder.add_frame(&chip8.screen);
+        }
+    });
+    result.map_err(error)?;
+    recorder.finish().map_err(error)?;
+    Ok(finish)
+}
+
+// The machine with the ROM loaded, from the options or from the movie
+fn start(args: &Args) -> Result<Headless, String> {
+    let rom = read_rom(&args.rom)?;
+    if let Some(path) = &args.movie {
+        let data = std::fs::read(path).map_err(|err| format!("Error reading {}: {}", path, err))?;
+        let movie = Movie::from_bytes(&data).map_err(|err| format!("{}: {}", path, err))?;
+        return Headless::from_movie(movie, &rom).map_err(|err| format!("{}: {}", path, err));
+    }
+    let mut chip8 = Chip8::with_quirks(args.quirks);
+    chip8.set_mode(args.mode);
+    chip8.rng = args.rng.with_seed(args.seed);
+    chip8.load(&rom)?;
+    Ok(Headless::new(chip8, args.instructions_per_frame))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            process::exit(EXIT_ERROR);
+        }
+    };
+
+    let mut headless = match start(&args) {
+        Ok(headless) => headless,
+        Err(err) => {
+            eprintln!("Error loading ROM: {}", err);
+            process::exit(EXIT_ERROR);
+        }
+    };
+    headless.stop_at = args.stop_at.clone();
+    headless.keys = args.keys.clone();
+    let movie_frames = headless.movie.as_ref().map(|movie| movie.len() as u64);
+    let frames = args.frames.or(movie_frames).unwrap_or(DEFAULT_FRAMES);
+    let finish = match &args.video {
+        Some((path, format)) => {
+            let colors = args.theme.colors();
+            match record(&mut headless, frames, path, *format, &colors, args.scale) {
+                Ok(finish) => finish,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        None => headless.run(frames),
+    };
+    // Status goes to stderr, stdout is for the dumps
+    eprintln!("{} after {} frames", finish, headless.frame());
+
+    if let Err(err) = dump(&args, &headless.chip8) {
+        eprintln!("{}", err);
+        process::exit(EXIT_ERROR);
+    }
+
+    let code = match finish {
+        Finish::Fault(_) => EXIT_FAULT,
+        Finish::Frames if !args.stop_at.is_empty() => EXIT_NOT_REACHED,
+        _ => 0,
+    };
+    process::exit(code);
+}
@@ -0,0 +1,1235 @@
+use std::cmp::Ordering;
+
+use crate::error::{CpuError, StepOutcome};
+use crate::instruction::{decode, Instruction};
+use crate::keyboard::{Chip8Key, Keyboard};
+use crate::mode::{Mode, CHIP8_MEMORY_CAPACITY};
+use crate::quirks::Quirks;
+use crate::random::Random;
+use crate::screen::Screen;
+
+const MEMORY_CAPACITY: usize = CHIP8_MEMORY_CAPACITY;
+pub(crate) const TOTAL_DATA_REGISTERS: usize = 16;
+
+pub(crate) const TOTAL_STACK_DEPTH: u8 = 16;
+const CHIP8_DEFAULT_SPRITE_HEIGHT: u16 = 5;
+const CHIP8_DEFAULT_CHARACTER_SET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0,
+    0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80,
+    0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
+    0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0, 0xF0, 0x80, 0x80, 0x80,
+    0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+// SUPER-CHIP 8x10 font, stored right after the default 4x5 one. XO-CHIP adds A-F.
+const CHIP8_BIG_SPRITE_HEIGHT: u16 = 10;
+const CHIP8_BIG_CHARACTER_SET_ADDRESS: u16 = 0x50;
+const CHIP8_BIG_CHARACTER_SET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x18, 0x78, 0x78, 0x18, 0x18, 0x18,
+    0x18, 0x18, 0xFF, 0xFF, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF,
+    0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03,
+    0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0, 0xC0,
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+    0x03, 0x03, 0xFF, 0xFF, 0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xFC, 0xFC,
+    0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3,
+    0xFF, 0x3C, 0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, 0xFF, 0xFF, 0xC0, 0xC0,
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0,
+];
+// SUPER-CHIP has 8 RPL user flags, XO-CHIP extends them to 16
+pub(crate) const TOTAL_RPL_FLAGS: usize = 16;
+// XO-CHIP audio: a 128 bit 1-bit sample pattern and a pitch register
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;
+// A 50% square wave, 500 Hz at the default pitch, so XO-CHIP programs that
+// beep with the sound timer alone are heard before they load a pattern
+const DEFAULT_AUDIO_PATTERN: [u8; AUDIO_PATTERN_SIZE] = [0xF0; AUDIO_PATTERN_SIZE];
+const LOAD_ADDRESS: u16 = 0x200;
+
+// unsigend short = u16
+// unsigend char = u8
+
+pub struct Chip8 {
+    pub(crate) memory: Vec<u8>,
+    pub(crate) v: [u8; TOTAL_DATA_REGISTERS], // Chip-8 has 16 general purpose 8-bit registers, usually referred to as Vx, where x is a hexadecimal digit (0 through F).
+    pub(crate) i: u16, // There is also a 16-bit register called I. This register is generally used to store memory addresses, so only the lowest (rightmost) 12 bits are usually used.
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub program_counter: u16,
+    pub(crate) stack_pointer: u8,
+    pub(crate) stack: [u16; TOTAL_STACK_DEPTH as usize],
+    pub keyboard: Keyboard,
+    pub screen: Screen,
+    pub quirks: Quirks,
+    pub(crate) mode: Mode,
+    pub(crate) rpl: [u8; TOTAL_RPL_FLAGS],
+    pub audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub pitch: u8,
+    // Key pressed while blocked in Fx0A, waiting for it to be released
+    pub(crate) key_wait: Option<Chip8Key>,
+    // Cxkk's source, seeded from the OS unless replaced
+    pub rng: Random,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8 {
+    pub fn new() -> Chip8 {
+        let memory = vec![0; MEMORY_CAPACITY];
+        let v = [0; TOTAL_DATA_REGISTERS];
+        let i = 0;
+        let delay_timer = 0;
+        let sound_timer = 0;
+        let program_counter = 0;
+        let stack_pointer = 0;
+        let stack = [0; TOTAL_STACK_DEPTH as usize];
+        let keyboard = Keyboard::new();
+        let screen = Screen::new();
+
+        let mut chip8 = Chip8 {
+            memory,
+            v,
+            i,
+            delay_timer,
+            sound_timer,
+            program_counter,
+            stack_pointer,
+            stack,
+            keyboard,
+            screen,
+            quirks: Quirks::default(),
+            mode: Mode::Chip8,
+            rpl: [0; TOTAL_RPL_FLAGS],
+            audio_pattern: DEFAULT_AUDIO_PATTERN,
+            pitch: DEFAULT_PITCH,
+            key_wait: None,
+            rng: Random::from_entropy(),
+        };
+
+        for (i, &value) in CHIP8_DEFAULT_CHARACTER_SET.iter().enumerate() {
+            chip8.memory[i] = value;
+        }
+        let big_font = CHIP8_BIG_CHARACTER_SET_ADDRESS as usize;
+        chip8.memory[big_font..big_font + CHIP8_BIG_CHARACTER_SET.len()]
+            .copy_from_slice(&CHIP8_BIG_CHARACTER_SET);
+        chip8
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.quirks = quirks;
+        chip8
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    // Resizes memory for the new mode. Shrinking drops anything above the
+    // CHIP-8 address space, so switch before loading a ROM.
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.memory.resize(mode.memory_capacity(), 0);
+    }
+
+    pub fn set_memory_addr(&mut self, index: usize, value: u8) -> Result<(), &str> {
+        match index.cmp(&self.memory.len()) {
+            Ordering::Less => {
+                self.memory[index] = value;
+                Ok(())
+            }
+            _ => Err("memory out of bounds!"),
+        }
+    }
+
+    pub fn get_memory_addr(&self, index: usize) -> Result<u8, &str> {
+        match index.cmp(&self.memory.len()) {
+            Ordering::Less => Ok(self.memory[index]),
+            _ => Err("memory out of bounds!"),
+        }
+    }
+
+    pub fn push_to_stack(&mut self, value: u16) -> Result<(), CpuError> {
+        if self.stack_pointer < TOTAL_STACK_DEPTH {
+            self.stack[self.stack_pointer as usize] = value;
+            self.stack_pointer += 1;
+            Ok(())
+        } else {
+            Err(CpuError::StackOverflow)
+        }
+    }
+
+    pub fn pop_from_stack(&mut self) -> Result<u16, CpuError> {
+        if self.stack_pointer > 0 {
+            self.stack_pointer -= 1;
+            Ok(self.stack[self.stack_pointer as usize])
+        } else {
+            Err(CpuError::StackUnderflow)
+        }
+    }
+
+    // Fetches the opcode at the program counter, advances past it and executes it
+    pub fn step(&mut self) -> Result<StepOutcome, CpuError> {
+        let opcode = self.memory_get_short(self.program_counter as usize)?;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        self.exec(opcode)
+    }
+
+    // Decrements the delay and sound timers, to be called at 60 Hz
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    // Runs one 60 Hz frame: up to `cycles` instructions, then a timer tick.
+    // Stops early if the program exits or blocks on Fx0A.
+    pub fn run_frame(&mut self, cycles: u32) -> Result<StepOutcome, CpuError> {
+        let mut outcome = StepOutcome::Continue;
+        for _ in 0..cycles {
+            outcome = self.step()?;
+            match outcome {
+                StepOutcome::Exit => return Ok(outcome),
+                // Nothing more will happen this frame, but the timers keep running
+                StepOutcome::WaitingForKey => break,
+                StepOutcome::Continue => {}
+            }
+        }
+        self.tick_timers();
+        Ok(outcome)
+    }
+
+    fn reset_vf_after_logic(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.v[0x0F] = 0;
+        }
+    }
+
+    fn skip_if(&mut self, condition: bool) -> Result<(), CpuError> {
+        if condition {
+            // XO-CHIP: skipping F000 NNNN skips all four of its bytes
+            let long_load = self.mode == Mode::XoChip
+                && self.memory_get_short(self.program_counter as usize)? == 0xF000;
+            let len = if long_load { 4 } else { 2 };
+            self.program_counter = self.program_counter.wrapping_add(len);
+        }
+        Ok(())
+    }
+
+    // Reads a byte the way an instruction would, faulting instead of panicking
+    fn read_byte(&self, addr: usize) -> Result<u8, CpuError> {
+        self.memory
+            .get(addr)
+            .copied()
+            .ok_or(CpuError::MemoryOutOfBounds { addr })
+    }
+
+    // Checks a whole access up front, so that an instruction that faults
+    // hasn't changed anything yet. Reports the first address out of bounds.
+    fn check_range(&self, start: usize, len: usize) -> Result<(), CpuError> {
+        if start + len > self.memory.len() {
+            return Err(CpuError::MemoryOutOfBounds {
+                addr: start.max(self.memory.len()),
+            });
+        }
+        Ok(())
+    }
+
+    fn write_byte(&mut self, addr: usize, value: u8) -> Result<(), CpuError> {
+        match self.memory.get_mut(addr) {
+            Some(byte) => {
+                *byte = value;
+                Ok(())
+            }
+            None => Err(CpuError::MemoryOutOfBounds { addr }),
+        }
+    }
+
+    fn unknown_opcode(&self, opcode: u16) -> CpuError {
+        CpuError::UnknownOpcode {
+            pc: self.program_counter.wrapping_sub(2),
+            opcode,
+        }
+    }
+
+    // Executes an already fetched opcode. The program counter is expected to
+    // point past it already, as it does after `step` fetches it.
+    pub fn exec(&mut self, opcode: u16) -> Result<StepOutcome, CpuError> {
+        self.execute(decode(opcode))
+    }
+
+    pub fn load(&mut self, buf: &[u8]) -> Result<(), &str> {
+        let load_address = LOAD_ADDRESS as usize;
+
+        if load_address + buf.len() > self.memory.len() {
+            return Err("program too large for memory");
+        }
+
+        self.memory[load_address..load_address + buf.len()].copy_from_slice(buf);
+        self.program_counter = LOAD_ADDRESS;
+
+        Ok(())
+    }
+
+    pub fn memory_get_short(&self, index: usize) -> Result<u16, CpuError> {
+        let byte1 = self.read_byte(index)? as u16;
+        let byte2 = self.read_byte(index + 1)? as u16;
+        Ok((byte1 << 8) | byte2)
+    }
+
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.v[y]
+        } else {
+            self.v[x]
+        }
+    }
+
+    pub fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome, CpuError> {
+        use Instruction::*;
+
+        if instruction.is_xo_chip() && self.mode != Mode::XoChip {
+            return Err(self.unknown_opcode(instruction.encode()));
+        }
+
+        match instruction {
+            Clear => self.screen.clear(),
+            Return => {
+                self.program_counter = self.pop_from_stack()?;
+            }
+            ScrollDown(n) => self.screen.scroll_down(n as usize),
+            ScrollUp(n) => self.screen.scroll_up(n as usize),
+            ScrollRight => self.screen.scroll_right(4),
+            ScrollLeft => self.screen.scroll_left(4),
+            Exit => return Ok(StepOutcome::Exit),
+            LowRes => self.screen.set_hires(false),
+            HighRes => self.screen.set_hires(true),
+
+            Jump(nnn) => self.program_counter = nnn,
+            Call(nnn) => {
+                self.push_to_stack(self.program_counter)?;
+                self.program_counter = nnn;
+            }
+
+            SkipEqualByte { x, kk } => self.skip_if(self.v[x as usize] == kk)?,
+            SkipNotEqualByte { x, kk } => self.skip_if(self.v[x as usize] != kk)?,
+            SkipEqual { x, y } => self.skip_if(self.v[x as usize] == self.v[y as usize])?,
+            SkipNotEqual { x, y } => self.skip_if(self.v[x as usize] != self.v[y as usize])?,
+
+            // Store or load the register range at I, I unchanged
+            SaveRange { x, y } => {
+                self.check_range(self.i as usize, x.abs_diff(y) as usize + 1)?;
+                for (offset, reg) in register_range(x as usize, y as usize).enumerate() {
+                    self.write_byte(self.i as usize + offset, self.v[reg])?;
+                }
+            }
+            LoadRange { x, y } => {
+                self.check_range(self.i as usize, x.abs_diff(y) as usize + 1)?;
+                for (offset, reg) in register_range(x as usize, y as usize).enumerate() {
+                    self.v[reg] = self.read_byte(self.i as usize + offset)?;
+                }
+            }
+
+            LoadByte { x, kk } => self.v[x as usize] = kk,
+            AddByte { x, kk } => self.v[x as usize] = self.v[x as usize].wrapping_add(kk),
+
+            Load { x, y } => self.v[x as usize] = self.v[y as usize],
+            Or { x, y } => {
+                self.v[x as usize] |= self.v[y as usize];
+                self.reset_vf_after_logic();
+            }
+            And { x, y } => {
+                self.v[x as usize] &= self.v[y as usize];
+                self.reset_vf_after_logic();
+            }
+            Xor { x, y } => {
+                self.v[x as usize] ^= self.v[y as usize];
+                self.reset_vf_after_logic();
+            }
+            // The flag ops write Vx first and VF last, so with x = F the
+            // flag is what's left in VF
+            //
+            // Set Vx = Vx + Vy, set VF = carry
+            Add { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
+                self.v[x] = sum;
+                self.v[0x0F] = carry as u8;
+            }
+            // Set Vx = Vx - Vy, set VF = Not borrow
+            Sub { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (difference, borrow) = self.v[x].overflowing_sub(self.v[y]);
+                self.v[x] = difference;
+                self.v[0x0F] = !borrow as u8;
+            }
+            // Set Vx = Vy - Vx, set VF = Not borrow
+            SubNegated { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (difference, borrow) = self.v[y].overflowing_sub(self.v[x]);
+                self.v[x] = difference;
+                self.v[0x0F] = !borrow as u8;
+            }
+            ShiftRight { x, y } => {
+                let source = self.shift_source(x as usize, y as usize);
+                self.v[x as usize] = source >> 1;
+                self.v[0x0F] = source & 0x01;
+            }
+            ShiftLeft { x, y } => {
+                let source = self.shift_source(x as usize, y as usize);
+                self.v[x as usize] = source << 1;
+                self.v[0x0F] = source >> 7;
+            }
+
+            LoadI(nnn) => self.i = nnn,
+            // Jump to nnn + V0, or nnn + Vx with the jump quirk
+            JumpOffset(nnn) => {
+                let offset = if self.quirks.jump_uses_vx {
+                    self.v[(nnn >> 8) as usize]
+                } else {
+                    self.v[0x00]
+                };
+                self.program_counter = nnn + offset as u16;
+            }
+            Random { x, kk } => {
+                let random_byte = self.rng.next_byte();
+                self.v[x as usize] = random_byte & kk;
+            }
+            // Dxy0 draws a 16x16 sprite in low resolution too, as XO-CHIP
+            // does. SUPER-CHIP 1.1 drew 8x16 there and the VIP nothing, but
+            // no known program relies on either.
+            Draw { x, y, n } => {
+                let start = self.i as usize;
+                let len = if n == 0 { 32 } else { n as usize };
+                // XO-CHIP: one sprite image per selected plane, back to back
+                let end = start + len * self.screen.selected_plane_count();
+                self.check_range(start, end - start)?;
+                let sprite = &self.memory[start..end];
+                let (x, y) = (self.v[x as usize].into(), self.v[y as usize].into());
+                let collision = if n == 0 {
+                    self.screen
+                        .draw_large_sprite(x, y, sprite, self.quirks.clip_sprites)
+                } else {
+                    self.screen.chip8_screen_draw_sprite(
+                        x,
+                        y,
+                        sprite,
+                        n.into(),
+                        self.quirks.clip_sprites,
+                    )
+                };
+                self.v[0x0F] = if collision { 1 } else { 0 };
+            }
+
+            // Only the low nibble of Vx names a key
+            SkipKeyPressed(x) => {
+                let key = (self.v[x as usize] & 0x0F) as usize;
+                self.skip_if(self.keyboard.is_key_index_down(key))?;
+            }
+            SkipKeyNotPressed(x) => {
+                let key = (self.v[x as usize] & 0x0F) as usize;
+                self.skip_if(!self.keyboard.is_key_index_down(key))?;
+            }
+
+            // Loads the following word into I
+            LoadLongI => {
+                self.i = self.memory_get_short(self.program_counter as usize)?;
+                self.program_counter = self.program_counter.wrapping_add(2);
+            }
+            Plane(n) => self.screen.select_planes(n),
+            // Loads the 16 byte audio pattern at I
+            Audio => {
+                let start = self.i as usize;
+                let end = start + AUDIO_PATTERN_SIZE;
+                self.check_range(start, AUDIO_PATTERN_SIZE)?;
+                self.audio_pattern.copy_from_slice(&self.memory[start..end]);
+            }
+            Pitch(x) => self.pitch = self.v[x as usize],
+
+            LoadDelay(x) => self.v[x as usize] = self.delay_timer,
+            // Halts until a key is pressed and released, like the VIP, by
+            // re-executing itself until then
+            WaitKey(x) => match self.key_wait {
+                None => {
+                    self.key_wait = self.keyboard.first_pressed();
+                    self.program_counter = self.program_counter.wrapping_sub(2);
+                    return Ok(StepOutcome::WaitingForKey);
+                }
+                Some(key) if self.keyboard.is_key_down(key) => {
+                    self.program_counter = self.program_counter.wrapping_sub(2);
+                    return Ok(StepOutcome::WaitingForKey);
+                }
+                Some(key) => {
+                    self.v[x as usize] = key.value();
+                    self.key_wait = None;
+                }
+            },
+            SetDelay(x) => self.delay_timer = self.v[x as usize],
+            SetSound(x) => self.sound_timer = self.v[x as usize],
+            AddI(x) => self.i = self.i.wrapping_add(self.v[x as usize] as u16),
+            LoadFont(x) => self.i = (self.v[x as usize] as u16) * CHIP8_DEFAULT_SPRITE_HEIGHT,
+            // Point I at the big font sprite for digit Vx
+            LoadBigFont(x) => {
+                self.i = CHIP8_BIG_CHARACTER_SET_ADDRESS
+                    + (self.v[x as usize] & 0x0F) as u16 * CHIP8_BIG_SPRITE_HEIGHT;
+            }
+            StoreBcd(x) => {
+                let value = self.v[x as usize];
+                let addr = self.i as usize;
+                self.check_range(addr, 3)?;
+                self.write_byte(addr, value / 100)?;
+                self.write_byte(addr + 1, (value / 10) % 10)?;
+                self.write_byte(addr + 2, value % 10)?;
+            }
+            StoreRegisters(x) => {
+                let x = x as usize;
+                self.check_range(self.i as usize, x + 1)?;
+                for i in 0..=x {
+                    self.write_byte(self.i as usize + i, self.v[i])?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            }
+            LoadRegisters(x) => {
+                let x = x as usize;
+                self.check_range(self.i as usize, x + 1)?;
+                for i in 0..=x {
+                    self.v[i] = self.read_byte(self.i as usize + i)?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            }
+            // Store V0..Vx in the RPL user flags, or read them back
+            // SUPER-CHIP 1.1 has 8 flag registers, XO-CHIP 16
+            StoreFlags(x) | LoadFlags(x) if x > 7 && self.mode != Mode::XoChip => {
+                return Err(self.unknown_opcode(instruction.encode()));
+            }
+            StoreFlags(x) => self.rpl[..=x as usize].copy_from_slice(&self.v[..=x as usize]),
+            LoadFlags(x) => self.v[..=x as usize].copy_from_slice(&self.rpl[..=x as usize]),
+
+            Unknown(opcode) => return Err(self.unknown_opcode(opcode)),
+        }
+        Ok(StepOutcome::Continue)
+    }
+}
+
+// Registers touched by 5xy2/5xy3, walking backwards when x > y
+fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+    if x <= y {
+        Box::new(x..=y)
+    } else {
+        Box::new((y..=x).rev())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_memory_addr_within_bounds() {
+        let mut chip8 = Chip8::new();
+        let index = 0;
+        let value = 0x90;
+        let result = chip8.set_memory_addr(index, value);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_set_memory_addr_out_of_bounds() {
+        let mut chip8 = Chip8::new();
+        let index = MEMORY_CAPACITY + 1;
+        let value = 0x90;
+        let result = chip8.set_memory_addr(index, value);
+        assert_eq!(result, Err("memory out of bounds!"));
+    }
+
+    #[test]
+    fn test_get_memory_addr_within_bounds() {
+        let mut chip8 = Chip8::new();
+        let index = 0;
+        chip8.set_memory_addr(index, 0x90).unwrap();
+        let result = chip8.get_memory_addr(index);
+        assert_eq!(result, Ok(0x90));
+    }
+
+    #[test]
+    fn test_get_memory_addr_out_of_bounds() {
+        let chip8 = Chip8::new();
+        let index = MEMORY_CAPACITY + 1;
+        let result = chip8.get_memory_addr(index);
+        assert_eq!(result, Err("memory out of bounds!"));
+    }
+
+    #[test]
+    fn test_push_to_stack_within_bounds() {
+        let mut chip8 = Chip8::new();
+        chip8.stack_pointer = 0;
+        let value = 0x200;
+        let result = chip8.push_to_stack(value);
+        assert_eq!(result, Ok(()));
+        assert_eq!(chip8.stack[0], 0x200);
+        assert_eq!(chip8.stack_pointer, 1);
+    }
+
+    #[test]
+    fn test_push_to_stack_out_of_bounds() {
+        let mut chip8 = Chip8::new();
+        chip8.stack_pointer = TOTAL_STACK_DEPTH; // Set the stack pointer to the maximum value
+        let value = 0x200;
+        let result = chip8.push_to_stack(value);
+        assert_eq!(result, Err(CpuError::StackOverflow));
+    }
+
+    #[test]
+    fn test_pop_from_stack_within_bounds() {
+        let mut chip8 = Chip8::new();
+        chip8.stack_pointer = 1;
+        chip8.stack[0] = 0x200;
+        let result = chip8.pop_from_stack();
+        assert_eq!(result, Ok(0x200));
+        assert_eq!(chip8.stack_pointer, 0);
+    }
+
+    #[test]
+    fn test_pop_from_stack_out_of_bounds() {
+        let mut chip8 = Chip8::new();
+        chip8.stack_pointer = 0; // Set the stack pointer to 0
+        let result = chip8.pop_from_stack();
+        assert_eq!(result, Err(CpuError::StackUnderflow));
+    }
+
+    #[test]
+    fn test_load_valid_program() {
+        let mut chip8 = Chip8::new();
+        let program: [u8; 4] = [0x12, 0x34, 0x56, 0x78];
+        let result = chip8.load(&program);
+        assert_eq!(result, Ok(()));
+        assert_eq!(chip8.program_counter, LOAD_ADDRESS);
+    }
+
+    #[test]
+    fn test_load_program_too_large() {
+        let mut chip8 = Chip8::new();
+        let program: [u8; MEMORY_CAPACITY + 1] = [0; MEMORY_CAPACITY + 1];
+        let result = chip8.load(&program);
+        assert_eq!(result, Err("program too large for memory"));
+    }
+
+    #[test]
+    fn test_exec_ret_with_empty_stack_underflows() {
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.exec(0x00EE), Err(CpuError::StackUnderflow));
+    }
+
+    #[test]
+    fn test_exec_call_with_full_stack_overflows() {
+        let mut chip8 = Chip8::new();
+        chip8.stack_pointer = TOTAL_STACK_DEPTH;
+        assert_eq!(chip8.exec(0x2300), Err(CpuError::StackOverflow));
+    }
+
+    #[test]
+    fn test_exec_unknown_opcode() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0xE0, 0x00]).unwrap();
+        let result = chip8.step();
+        assert_eq!(
+            result,
+            Err(CpuError::UnknownOpcode {
+                pc: LOAD_ADDRESS,
+                opcode: 0xE000
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_fetch_at_end_of_memory() {
+        let mut chip8 = Chip8::new();
+        chip8.program_counter = (MEMORY_CAPACITY - 1) as u16;
+        assert_eq!(
+            chip8.step(),
+            Err(CpuError::MemoryOutOfBounds {
+                addr: MEMORY_CAPACITY
+            })
+        );
+    }
+
+    #[test]
+    fn test_exec_store_registers_out_of_bounds() {
+        let mut chip8 = Chip8::new();
+        chip8.i = (MEMORY_CAPACITY - 2) as u16;
+        assert_eq!(
+            chip8.exec(0xF355),
+            Err(CpuError::MemoryOutOfBounds {
+                addr: MEMORY_CAPACITY
+            })
+        );
+    }
+
+    #[test]
+    fn test_shift_quirk() {
+        let mut chip8 = Chip8::new();
+        chip8.v[1] = 0x01;
+        chip8.v[2] = 0x82;
+        chip8.exec(0x8126).unwrap();
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0x00, 1));
+
+        let mut chip8 = Chip8::with_quirks(Quirks::vip());
+        chip8.v[1] = 0x01;
+        chip8.v[2] = 0x82;
+        chip8.exec(0x8126).unwrap();
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0x41, 0));
+    }
+
+    #[test]
+    fn test_load_store_quirk() {
+        let mut chip8 = Chip8::new();
+        chip8.i = 0x300;
+        chip8.exec(0xF255).unwrap();
+        assert_eq!(chip8.i, 0x300);
+
+        let mut chip8 = Chip8::with_quirks(Quirks::vip());
+        chip8.i = 0x300;
+        chip8.exec(0xF255).unwrap();
+        assert_eq!(chip8.i, 0x303);
+    }
+
+    #[test]
+    fn test_jump_quirk() {
+        let mut chip8 = Chip8::with_quirks(Quirks::schip());
+        chip8.v[0] = 0x10;
+        chip8.v[2] = 0x20;
+        chip8.exec(0xB234).unwrap();
+        assert_eq!(chip8.program_counter, 0x254);
+    }
+
+    #[test]
+    fn test_logic_quirk() {
+        let mut chip8 = Chip8::with_quirks(Quirks::vip());
+        chip8.v[0xF] = 5;
+        chip8.exec(0x8011).unwrap();
+        assert_eq!(chip8.v[0xF], 0);
+    }
+
+    #[test]
+    fn test_clip_quirk() {
+        let mut chip8 = Chip8::with_quirks(Quirks::vip());
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 0xFF;
+        chip8.v[0] = 60;
+        chip8.exec(0xD011).unwrap();
+        assert!(chip8.screen.is_set(63, 0));
+        assert!(!chip8.screen.is_set(0, 0));
+
+        chip8.quirks = Quirks::xochip();
+        chip8.screen.clear();
+        chip8.exec(0xD011).unwrap();
+        assert!(chip8.screen.is_set(0, 0));
+    }
+
+    #[test]
+    fn test_schip_resolution_switch() {
+        let mut chip8 = Chip8::new();
+        chip8.exec(0x00FF).unwrap();
+        assert_eq!((chip8.screen.width(), chip8.screen.height()), (128, 64));
+        chip8.exec(0x00FE).unwrap();
+        assert_eq!((chip8.screen.width(), chip8.screen.height()), (64, 32));
+    }
+
+    #[test]
+    fn test_schip_large_sprite_and_scroll() {
+        let mut chip8 = Chip8::new();
+        chip8.exec(0x00FF).unwrap();
+        chip8.i = 0x300;
+        chip8.memory[0x300..0x320].copy_from_slice(&[0xFF; 32]);
+        chip8.exec(0xD000).unwrap();
+        assert!(chip8.screen.is_set(15, 15));
+        assert!(!chip8.screen.is_set(16, 0));
+
+        chip8.exec(0x00C2).unwrap();
+        assert!(!chip8.screen.is_set(0, 1));
+        assert!(chip8.screen.is_set(0, 17));
+        chip8.exec(0x00FB).unwrap();
+        assert!(!chip8.screen.is_set(3, 2));
+        assert!(chip8.screen.is_set(19, 2));
+        chip8.exec(0x00FC).unwrap();
+        assert!(chip8.screen.is_set(0, 2));
+    }
+
+    #[test]
+    fn test_schip_big_font_and_rpl_flags() {
+        let mut chip8 = Chip8::new();
+        chip8.v[3] = 0x02;
+        chip8.exec(0xF330).unwrap();
+        assert_eq!(chip8.i, CHIP8_BIG_CHARACTER_SET_ADDRESS + 20);
+
+        chip8.v[0..4].copy_from_slice(&[1, 2, 3, 4]);
+        chip8.exec(0xF375).unwrap();
+        chip8.v[0..4].copy_from_slice(&[0; 4]);
+        chip8.exec(0xF285).unwrap();
+        assert_eq!(chip8.v[0..4], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_rpl_flags_beyond_v7_need_xochip() {
+        let mut chip8 = Chip8::new();
+        assert!(matches!(
+            chip8.exec(0xF875),
+            Err(CpuError::UnknownOpcode { opcode: 0xF875, .. })
+        ));
+        assert!(chip8.exec(0xF785).is_ok());
+
+        let mut chip8 = xochip();
+        chip8.v[15] = 9;
+        chip8.exec(0xFF75).unwrap();
+        chip8.v[15] = 0;
+        chip8.exec(0xFF85).unwrap();
+        assert_eq!(chip8.v[15], 9);
+    }
+
+    #[test]
+    fn test_schip_exit() {
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.exec(0x00FD), Ok(StepOutcome::Exit));
+    }
+
+    fn xochip() -> Chip8 {
+        let mut chip8 = Chip8::with_quirks(Quirks::xochip());
+        chip8.set_mode(Mode::XoChip);
+        chip8
+    }
+
+    #[test]
+    fn test_xochip_memory_and_long_load() {
+        let mut chip8 = xochip();
+        assert_eq!(chip8.set_memory_addr(0xFFFF, 1), Ok(()));
+        chip8.load(&[0xF0, 0x00, 0xAB, 0xCD]).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0xABCD);
+        assert_eq!(chip8.program_counter, LOAD_ADDRESS + 4);
+
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0xF0, 0x00]).unwrap();
+        assert!(chip8.step().is_err());
+    }
+
+    #[test]
+    fn test_xochip_skip_over_long_load() {
+        let mut chip8 = xochip();
+        chip8.load(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34]).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.program_counter, LOAD_ADDRESS + 6);
+    }
+
+    #[test]
+    fn test_xochip_register_range_save_load() {
+        let mut chip8 = xochip();
+        chip8.i = 0x400;
+        chip8.v[2..5].copy_from_slice(&[7, 8, 9]);
+        chip8.exec(0x5422).unwrap();
+        assert_eq!(chip8.memory[0x400..0x403], [9, 8, 7]);
+        assert_eq!(chip8.i, 0x400);
+
+        chip8.exec(0x5683).unwrap();
+        assert_eq!(chip8.v[6..9], [9, 8, 7]);
+    }
+
+    #[test]
+    fn test_xochip_planes() {
+        let mut chip8 = xochip();
+        chip8.i = 0x400;
+        chip8.memory[0x400] = 0x80;
+        chip8.memory[0x401] = 0xC0;
+        chip8.exec(0xF301).unwrap();
+        chip8.exec(0xD001).unwrap();
+        assert_eq!(chip8.screen.pixel(0, 0), 3);
+        assert_eq!(chip8.screen.pixel(1, 0), 2);
+
+        chip8.exec(0xF201).unwrap();
+        chip8.exec(0x00E0).unwrap();
+        assert_eq!(chip8.screen.pixel(0, 0), 1);
+        assert_eq!(chip8.screen.pixel(1, 0), 0);
+    }
+
+    #[test]
+    fn test_xochip_scroll_up_and_audio() {
+        let mut chip8 = xochip();
+        chip8.screen.set_screen(0, 5);
+        chip8.exec(0x00D2).unwrap();
+        assert!(chip8.screen.is_set(0, 3));
+
+        chip8.i = 0x400;
+        chip8.memory[0x400..0x410].copy_from_slice(&[0xAA; 16]);
+        chip8.exec(0xF002).unwrap();
+        assert_eq!(chip8.audio_pattern, [0xAA; 16]);
+        chip8.v[1] = 100;
+        chip8.exec(0xF13A).unwrap();
+        assert_eq!(chip8.pitch, 100);
+    }
+
+    #[test]
+    fn test_run_frame_ticks_timers_once() {
+        let mut chip8 = Chip8::new();
+        // 1200 - JP 200, an infinite loop
+        chip8.load(&[0x12, 0x00]).unwrap();
+        chip8.delay_timer = 2;
+        chip8.sound_timer = 1;
+        chip8.run_frame(10).unwrap();
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (1, 0));
+        chip8.run_frame(10).unwrap();
+        chip8.run_frame(10).unwrap();
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (0, 0));
+    }
+
+    #[test]
+    fn test_wait_for_key_blocks_until_release() {
+        let mut chip8 = Chip8::new();
+        // F30A - LD V3, K
+        chip8.load(&[0xF3, 0x0A]).unwrap();
+        chip8.delay_timer = 5;
+        assert_eq!(chip8.run_frame(10), Ok(StepOutcome::WaitingForKey));
+        assert_eq!(chip8.program_counter, LOAD_ADDRESS);
+        assert_eq!(chip8.delay_timer, 4);
+
+        chip8.keyboard.key_down(Chip8Key::KeyA);
+        assert_eq!(chip8.step(), Ok(StepOutcome::WaitingForKey));
+        assert_eq!(chip8.step(), Ok(StepOutcome::WaitingForKey));
+        assert_eq!(chip8.program_counter, LOAD_ADDRESS);
+
+        chip8.keyboard.key_up(Chip8Key::KeyA);
+        assert_eq!(chip8.step(), Ok(StepOutcome::Continue));
+        assert_eq!(chip8.v[3], 0xA);
+        assert_eq!(chip8.program_counter, LOAD_ADDRESS + 2);
+    }
+
+    #[test]
+    fn test_skip_if_key_uses_register_value() {
+        let mut chip8 = Chip8::new();
+        chip8.keyboard.key_down(Chip8Key::Key7);
+        chip8.v[2] = 0x7;
+        chip8.program_counter = 0x300;
+        // E29E - SKP V2
+        chip8.exec(0xE29E).unwrap();
+        assert_eq!(chip8.program_counter, 0x302);
+        // E2A1 - SKNP V2
+        chip8.exec(0xE2A1).unwrap();
+        assert_eq!(chip8.program_counter, 0x302);
+        // E7A1 - SKNP V7, V7 holds 0 and key 0 is up
+        chip8.exec(0xE7A1).unwrap();
+        assert_eq!(chip8.program_counter, 0x304);
+    }
+
+    // An 8xyN case: Vx and Vy going in, Vx and VF coming out. `flag` is None
+    // for instructions that leave VF alone.
+    struct AluCase {
+        quirks: Quirks,
+        n: u16,
+        vx: u8,
+        vy: u8,
+        result: u8,
+        flag: Option<u8>,
+    }
+
+    fn alu(n: u16, vx: u8, vy: u8, result: u8, flag: Option<u8>) -> AluCase {
+        AluCase {
+            quirks: Quirks::default(),
+            n,
+            vx,
+            vy,
+            result,
+            flag,
+        }
+    }
+
+    fn alu_cases() -> [AluCase; 27] {
+        [
+            alu(0x0, 0x12, 0x34, 0x34, None),
+            alu(0x1, 0x0F, 0x30, 0x3F, None),
+            alu(0x2, 0x3C, 0x0F, 0x0C, None),
+            alu(0x3, 0x3C, 0x0F, 0x33, None),
+            alu(0x4, 0x10, 0x20, 0x30, Some(0)),
+            alu(0x4, 0xF0, 0x20, 0x10, Some(1)),
+            alu(0x4, 0x80, 0x80, 0x00, Some(1)),
+            alu(0x4, 0xFF, 0x01, 0x00, Some(1)),
+            alu(0x4, 0xFF, 0x00, 0xFF, Some(0)),
+            alu(0x5, 0x30, 0x10, 0x20, Some(1)),
+            alu(0x5, 0x10, 0x30, 0xE0, Some(0)),
+            alu(0x5, 0x10, 0x10, 0x00, Some(1)),
+            alu(0x5, 0x00, 0x01, 0xFF, Some(0)),
+            alu(0x5, 0xFF, 0x00, 0xFF, Some(1)),
+            alu(0x6, 0x05, 0xAA, 0x02, Some(1)),
+            alu(0x6, 0x04, 0xAA, 0x02, Some(0)),
+            alu(0x6, 0x01, 0xAA, 0x00, Some(1)),
+            alu(0x7, 0x10, 0x30, 0x20, Some(1)),
+            alu(0x7, 0x30, 0x10, 0xE0, Some(0)),
+            alu(0x7, 0x10, 0x10, 0x00, Some(1)),
+            alu(0x7, 0x01, 0x00, 0xFF, Some(0)),
+            alu(0xE, 0x81, 0xAA, 0x02, Some(1)),
+            alu(0xE, 0x41, 0xAA, 0x82, Some(0)),
+            alu(0xE, 0x80, 0xAA, 0x00, Some(1)),
+            // Quirks that change where the operand or the flag comes from
+            AluCase {
+                quirks: Quirks::vip(),
+                ..alu(0x6, 0xAA, 0x05, 0x02, Some(1))
+            },
+            AluCase {
+                quirks: Quirks::vip(),
+                ..alu(0xE, 0xAA, 0x41, 0x82, Some(0))
+            },
+            AluCase {
+                quirks: Quirks::vip(),
+                ..alu(0x1, 0x0F, 0x30, 0x3F, Some(0))
+            },
+        ]
+    }
+
+    // VF's value going in when it isn't an operand, to tell "left alone" from 0 or 1
+    const VF_SENTINEL: u8 = 0x55;
+
+    #[test]
+    fn test_alu_table() {
+        for case in &alu_cases() {
+            let label = format!(
+                "8xy{:X} with Vx={:#04X} Vy={:#04X}",
+                case.n, case.vx, case.vy
+            );
+
+            // Vx and Vy are ordinary registers
+            let mut chip8 = Chip8::with_quirks(case.quirks);
+            chip8.v[1] = case.vx;
+            chip8.v[2] = case.vy;
+            chip8.v[0xF] = VF_SENTINEL;
+            chip8.exec(0x8120 | case.n).unwrap();
+            assert_eq!(chip8.v[1], case.result, "{}: result", label);
+            assert_eq!(
+                chip8.v[0xF],
+                case.flag.unwrap_or(VF_SENTINEL),
+                "{}: VF",
+                label
+            );
+            assert_eq!(chip8.v[2], case.vy, "{}: Vy changed", label);
+
+            // x = F: the flag is written last and wins over the result
+            let mut chip8 = Chip8::with_quirks(case.quirks);
+            chip8.v[0xF] = case.vx;
+            chip8.v[2] = case.vy;
+            chip8.exec(0x8F20 | case.n).unwrap();
+            let expected = case.flag.unwrap_or(case.result);
+            assert_eq!(chip8.v[0xF], expected, "{} into VF", label);
+
+            // y = F: VF is read as the operand before the flag overwrites it
+            let mut chip8 = Chip8::with_quirks(case.quirks);
+            chip8.v[1] = case.vx;
+            chip8.v[0xF] = case.vy;
+            chip8.exec(0x81F0 | case.n).unwrap();
+            assert_eq!(chip8.v[1], case.result, "{} from VF: result", label);
+            assert_eq!(
+                chip8.v[0xF],
+                case.flag.unwrap_or(case.vy),
+                "{} from VF",
+                label
+            );
+        }
+    }
+
+    #[test]
+    fn test_add_byte_wraps_without_touching_vf() {
+        let mut chip8 = Chip8::new();
+        chip8.v[3] = 0xFF;
+        chip8.v[0xF] = VF_SENTINEL;
+        chip8.exec(0x7302).unwrap();
+        assert_eq!((chip8.v[3], chip8.v[0xF]), (0x01, VF_SENTINEL));
+    }
+
+    #[test]
+    fn test_skip_table() {
+        // (opcode, V1, V2, skipped)
+        let cases = [
+            (0x3142, 0x42, 0x00, true),
+            (0x3142, 0x41, 0x00, false),
+            (0x4142, 0x42, 0x00, false),
+            (0x4142, 0x41, 0x00, true),
+            (0x5120, 0x07, 0x07, true),
+            (0x5120, 0x07, 0x08, false),
+            (0x9120, 0x07, 0x07, false),
+            (0x9120, 0x07, 0x08, true),
+        ];
+        for (opcode, v1, v2, skipped) in cases {
+            let mut chip8 = Chip8::new();
+            chip8.program_counter = 0x300;
+            chip8.v[1] = v1;
+            chip8.v[2] = v2;
+            chip8.exec(opcode).unwrap();
+            let expected = if skipped { 0x302 } else { 0x300 };
+            assert_eq!(chip8.program_counter, expected, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_bcd_table() {
+        for (value, digits) in [
+            (0, [0, 0, 0]),
+            (7, [0, 0, 7]),
+            (42, [0, 4, 2]),
+            (100, [1, 0, 0]),
+            (109, [1, 0, 9]),
+            (255, [2, 5, 5]),
+        ] {
+            let mut chip8 = Chip8::new();
+            chip8.i = 0x300;
+            chip8.v[4] = value;
+            chip8.exec(0xF433).unwrap();
+            assert_eq!(chip8.memory[0x300..0x303], digits, "BCD of {}", value);
+            assert_eq!(chip8.i, 0x300);
+        }
+    }
+
+    #[test]
+    fn test_add_i_overflow() {
+        // I is 16 bits wide: going past 0xFFF isn't masked and doesn't touch VF
+        let mut chip8 = Chip8::new();
+        chip8.i = 0x0FFF;
+        chip8.v[2] = 0x01;
+        chip8.v[0xF] = VF_SENTINEL;
+        chip8.exec(0xF21E).unwrap();
+        assert_eq!((chip8.i, chip8.v[0xF]), (0x1000, VF_SENTINEL));
+
+        chip8.i = 0xFFFF;
+        chip8.v[2] = 0x02;
+        chip8.exec(0xF21E).unwrap();
+        assert_eq!(chip8.i, 0x0001);
+    }
+
+    #[test]
+    fn test_jump_offset_table() {
+        // (quirks, opcode, V0, V3, target)
+        let cases = [
+            (Quirks::default(), 0xB300, 0x00, 0x40, 0x300),
+            (Quirks::default(), 0xB300, 0x20, 0x40, 0x320),
+            (Quirks::default(), 0xBFFF, 0xFF, 0x00, 0x10FE),
+            (Quirks::schip(), 0xB300, 0x20, 0x40, 0x340),
+            (Quirks::schip(), 0xB3FF, 0x20, 0xFF, 0x4FE),
+        ];
+        for (quirks, opcode, v0, v3, target) in cases {
+            let mut chip8 = Chip8::with_quirks(quirks);
+            chip8.v[0] = v0;
+            chip8.v[3] = v3;
+            chip8.exec(opcode).unwrap();
+            assert_eq!(
+                chip8.program_counter, target,
+                "{:04X} with {}",
+                opcode, quirks
+            );
+        }
+    }
+
+    #[test]
+    fn test_load_and_flow_opcodes() {
+        let mut chip8 = Chip8::new();
+        chip8.program_counter = 0x202;
+        chip8.exec(0x6A7F).unwrap();
+        assert_eq!(chip8.v[0xA], 0x7F);
+        chip8.exec(0x8BA0).unwrap();
+        assert_eq!(chip8.v[0xB], 0x7F);
+        chip8.exec(0xA123).unwrap();
+        assert_eq!(chip8.i, 0x123);
+        chip8.exec(0x1456).unwrap();
+        assert_eq!(chip8.program_counter, 0x456);
+
+        chip8.exec(0x2800).unwrap();
+        assert_eq!((chip8.program_counter, chip8.stack_pointer), (0x800, 1));
+        assert_eq!(chip8.stack[0], 0x456);
+        chip8.exec(0x00EE).unwrap();
+        assert_eq!((chip8.program_counter, chip8.stack_pointer), (0x456, 0));
+    }
+
+    #[test]
+    fn test_timer_and_font_opcodes() {
+        let mut chip8 = Chip8::new();
+        chip8.v[1] = 30;
+        chip8.exec(0xF115).unwrap();
+        chip8.exec(0xF118).unwrap();
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (30, 30));
+        chip8.delay_timer = 12;
+        chip8.exec(0xF207).unwrap();
+        assert_eq!(chip8.v[2], 12);
+
+        // Only the low nibble picks the digit for the big font, the small
+        // font multiplies the whole value
+        chip8.v[3] = 0xB;
+        chip8.exec(0xF329).unwrap();
+        assert_eq!(chip8.i, 0xB * 5);
+        chip8.v[3] = 0x1B;
+        chip8.exec(0xF330).unwrap();
+        assert_eq!(chip8.i, CHIP8_BIG_CHARACTER_SET_ADDRESS + 0xB * 10);
+    }
+
+    #[test]
+    fn test_random_respects_mask() {
+        let mut chip8 = Chip8::new();
+        for _ in 0..100 {
+            chip8.exec(0xC50F).unwrap();
+            assert_eq!(chip8.v[5] & 0xF0, 0);
+            chip8.exec(0xC600).unwrap();
+            assert_eq!(chip8.v[6], 0);
+        }
+    }
+
+    #[test]
+    fn test_random_is_reproducible_with_a_seed() {
+        let draws = |rng: Random| {
+            let mut chip8 = Chip8::new();
+            chip8.rng = rng;
+            (0..32)
+                .map(|_| {
+                    chip8.exec(0xC0FF).unwrap();
+                    chip8.v[0]
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(draws(Random::seeded(1)), draws(Random::seeded(1)));
+        assert_ne!(draws(Random::seeded(1)), draws(Random::seeded(2)));
+    }
+
+    #[test]
+    fn test_draw_sets_vf_on_collision_only() {
+        let mut chip8 = Chip8::new();
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 0xC0;
+        chip8.v[0xF] = VF_SENTINEL;
+        chip8.exec(0xD011).unwrap();
+        assert_eq!(chip8.v[0xF], 0);
+        assert!(chip8.screen.is_set(0, 0) && chip8.screen.is_set(1, 0));
+        chip8.exec(0xD011).unwrap();
+        assert_eq!(chip8.v[0xF], 1);
+        assert!(!chip8.screen.is_set(0, 0));
+    }
+
+    #[test]
+    fn test_store_load_registers_round_trip() {
+        for quirks in [Quirks::default(), Quirks::vip()] {
+            let mut chip8 = Chip8::with_quirks(quirks);
+            chip8.i = 0x300;
+            chip8.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+            chip8.v[4] = 0xEE;
+            chip8.exec(0xF355).unwrap();
+            assert_eq!(chip8.memory[0x300..0x305], [1, 2, 3, 4, 0]);
+
+            chip8.i = 0x300;
+            chip8.v = [0; TOTAL_DATA_REGISTERS];
+            chip8.exec(0xF365).unwrap();
+            assert_eq!(chip8.v[..5], [1, 2, 3, 4, 0]);
+            let expected_i = if quirks.load_store_increments_i {
+                0x304
+            } else {
+                0x300
+            };
+            assert_eq!(chip8.i, expected_i);
+        }
+    }
+}
@@ -0,0 +1,728 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::chip::{Chip8, AUDIO_PATTERN_SIZE};
+use crate::error::{CpuError, StepOutcome};
+use crate::instruction::{decode, Instruction};
+use crate::mode::Mode;
+
+// Pauses, single-steps and breaks a `Chip8`. A frontend drives the machine
+// through `Debugger::run_frame` instead of `Chip8::run_frame`, and feeds it
+// `Command`s typed by the user.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    paused: bool,
+    target: Option<RunTarget>,
+    // Set when resuming, so the instruction we stopped on doesn't stop us again
+    resume_from: Option<u16>,
+    stop: Option<StopReason>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: u16,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: Range<usize>,
+    pub access: Access,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn matches(self, actual: Access) -> bool {
+        self == Access::ReadWrite || self == actual
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunTarget {
+    // Step over: back at this address with the stack as deep as it was
+    ReturnTo { pc: u16, stack_pointer: u8 },
+    // Run to return: the stack got shallower than this
+    Return { stack_pointer: u8 },
+}
+
+// Why the debugger paused the machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint { addr: u16 },
+    Watchpoint { addr: usize, access: Access },
+    Stepped,
+    Returned,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint { addr } => write!(f, "breakpoint at {:#05X}", addr),
+            StopReason::Watchpoint { addr, access } => {
+                let verb = match access {
+                    Access::Write => "write to",
+                    _ => "read of",
+                };
+                write!(f, "watchpoint: {} {:#05X}", verb, addr)
+            }
+            StopReason::Stepped => write!(f, "stepped over call"),
+            StopReason::Returned => write!(f, "returned from subroutine"),
+        }
+    }
+}
+
+// `V3 == 0x10`, `I >= 0x300`, `DT != 0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub operand: Operand,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    V(usize),
+    I,
+    DelayTimer,
+    SoundTimer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+// Longer operators first, so `<=` isn't read as `<`
+const COMPARISONS: [(&str, Comparison); 6] = [
+    ("==", Comparison::Equal),
+    ("!=", Comparison::NotEqual),
+    ("<=", Comparison::LessOrEqual),
+    (">=", Comparison::GreaterOrEqual),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+impl Condition {
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        let actual = match self.operand {
+            Operand::V(reg) => chip8.v[reg] as u16,
+            Operand::I => chip8.i,
+            Operand::DelayTimer => chip8.delay_timer as u16,
+            Operand::SoundTimer => chip8.sound_timer as u16,
+        };
+        match self.comparison {
+            Comparison::Equal => actual == self.value,
+            Comparison::NotEqual => actual != self.value,
+            Comparison::Less => actual < self.value,
+            Comparison::LessOrEqual => actual <= self.value,
+            Comparison::Greater => actual > self.value,
+            Comparison::GreaterOrEqual => actual >= self.value,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (operator, comparison) = COMPARISONS
+            .iter()
+            .find(|(operator, _)| text.contains(operator))
+            .ok_or(format!("no comparison in condition '{}'", text))?;
+        let (left, right) = text.split_once(operator).unwrap();
+        let operand = match left.trim().to_ascii_uppercase().as_str() {
+            "I" => Operand::I,
+            "DT" => Operand::DelayTimer,
+            "ST" => Operand::SoundTimer,
+            reg => match reg.strip_prefix('V').map(|n| u8::from_str_radix(n, 16)) {
+                Some(Ok(n)) if n < 16 => Operand::V(n as usize),
+                _ => return Err(format!("unknown register '{}'", left.trim())),
+            },
+        };
+        Ok(Condition {
+            operand,
+            comparison: *comparison,
+            value: parse_number(right.trim())?,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.operand {
+            Operand::V(reg) => write!(f, "V{:X}", reg)?,
+            Operand::I => write!(f, "I")?,
+            Operand::DelayTimer => write!(f, "DT")?,
+            Operand::SoundTimer => write!(f, "ST")?,
+        }
+        let (operator, _) = COMPARISONS
+            .iter()
+            .find(|(_, comparison)| *comparison == self.comparison)
+            .unwrap();
+        write!(f, " {} {:#X}", operator, self.value)
+    }
+}
+
+// Numbers are decimal, or hex with a 0x or $ prefix
+pub(crate) fn parse_number(text: &str) -> Result<u16, String> {
+    let parsed = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix('$')) {
+        u16::from_str_radix(hex, 16)
+    } else {
+        text.parse()
+    };
+    parsed.map_err(|_| format!("invalid number '{}'", text))
+}
+
+// What a user can type at the debugger prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Continue,
+    Step,
+    StepOver,
+    Finish,
+    Break(Breakpoint),
+    Watch(Watchpoint),
+    Delete(usize),
+    List,
+    Registers,
+    Help,
+}
+
+pub const DEBUGGER_HELP: &str = "commands:
+  p, pause                    pause execution
+  c, continue                 resume execution
+  s, step                     execute one instruction
+  n, next                     step, running CALLs through to their return
+  f, finish                   run until the current subroutine returns
+  b, break <addr> [if <cond>] break at an address, optionally when e.g. V3 == 0x10
+  w, watch <addr>[-<end>] [r|w|rw]
+                              break when an instruction accesses memory (default w)
+  d, delete <n>               remove breakpoint or watchpoint n, as listed
+  l, list                     list breakpoints and watchpoints
+  r, regs                     show registers, stack, timers and the next instruction
+  h, help                     show this help";
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let command = match name {
+            "p" | "pause" => Command::Pause,
+            "c" | "continue" => Command::Continue,
+            "s" | "step" => Command::Step,
+            "n" | "next" => Command::StepOver,
+            "f" | "finish" => Command::Finish,
+            "l" | "list" => Command::List,
+            "r" | "regs" => Command::Registers,
+            "h" | "help" => Command::Help,
+            "b" | "break" => {
+                let (addr, condition) = match rest.split_once(" if ") {
+                    Some((addr, condition)) => (addr, Some(condition.parse()?)),
+                    None => (rest, None),
+                };
+                Command::Break(Breakpoint {
+                    addr: parse_number(addr.trim())?,
+                    condition,
+                })
+            }
+            "w" | "watch" => {
+                let (range, access) = rest.split_once(' ').unwrap_or((rest, "w"));
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (parse_number(start)?, parse_number(end)?),
+                    None => (parse_number(range)?, parse_number(range)?),
+                };
+                if end < start {
+                    return Err(format!("empty address range '{}'", range));
+                }
+                let access = match access.trim() {
+                    "r" => Access::Read,
+                    "w" => Access::Write,
+                    "rw" => Access::ReadWrite,
+                    other => {
+                        return Err(format!("unknown access '{}', expected r, w or rw", other))
+                    }
+                };
+                Command::Watch(Watchpoint {
+                    range: start as usize..end as usize + 1,
+                    access,
+                })
+            }
+            "d" | "delete" => {
+                let n = rest
+                    .parse()
+                    .map_err(|_| format!("invalid breakpoint number '{}'", rest))?;
+                Command::Delete(n)
+            }
+            _ => return Err(format!("unknown command '{}', try 'help'", name)),
+        };
+        Ok(command)
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.target = None;
+    }
+
+    pub fn resume(&mut self, chip8: &Chip8) {
+        self.paused = false;
+        self.resume_from = Some(chip8.program_counter);
+    }
+
+    // Why the machine last stopped, if nobody has asked yet
+    pub fn take_stop(&mut self) -> Option<StopReason> {
+        self.stop.take()
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    // Breakpoints and watchpoints share one numbering, breakpoints first
+    pub fn delete(&mut self, n: usize) -> bool {
+        if n < self.breakpoints.len() {
+            self.breakpoints.remove(n);
+        } else if n - self.breakpoints.len() < self.watchpoints.len() {
+            self.watchpoints.remove(n - self.breakpoints.len());
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // Executes exactly one instruction, ignoring breakpoints, and stays paused
+    pub fn step(&mut self, chip8: &mut Chip8) -> Result<StepOutcome, CpuError> {
+        self.pause();
+        chip8.step()
+    }
+
+    // Like `step`, but a CALL runs until it returns to the next instruction
+    pub fn step_over(&mut self, chip8: &mut Chip8) -> Result<StepOutcome, CpuError> {
+        let opcode = chip8.memory_get_short(chip8.program_counter as usize)?;
+        if opcode & 0xF000 != 0x2000 {
+            return self.step(chip8);
+        }
+        self.resume(chip8);
+        self.target = Some(RunTarget::ReturnTo {
+            pc: chip8.program_counter + 2,
+            stack_pointer: chip8.stack_pointer,
+        });
+        Ok(StepOutcome::Continue)
+    }
+
+    // Runs until the subroutine we're in returns. Fails outside of one.
+    pub fn finish(&mut self, chip8: &Chip8) -> bool {
+        if chip8.stack_pointer == 0 {
+            return false;
+        }
+        self.resume(chip8);
+        self.target = Some(RunTarget::Return {
+            stack_pointer: chip8.stack_pointer,
+        });
+        true
+    }
+
+    // Stands in for `Chip8::run_frame`. While paused nothing runs, timers
+    // included. A frame cut short by a breakpoint doesn't tick the timers.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, cycles: u32) -> Result<StepOutcome, CpuError> {
+        if self.paused {
+            return Ok(StepOutcome::Continue);
+        }
+        let mut outcome = StepOutcome::Continue;
+        for _ in 0..cycles {
+            let resuming = self.resume_from.take() == Some(chip8.program_counter);
+            if !resuming {
+                if let Some(reason) = self.check_stop(chip8)? {
+                    self.stop_with(reason);
+                    return Ok(StepOutcome::Continue);
+                }
+            }
+            outcome = chip8.step()?;
+            if let Some(reason) = self.check_target(chip8) {
+                self.stop_with(reason);
+                return Ok(outcome);
+            }
+            match outcome {
+                StepOutcome::Exit => return Ok(outcome),
+                StepOutcome::WaitingForKey => break,
+                StepOutcome::Continue => {}
+            }
+        }
+        chip8.tick_timers();
+        Ok(outcome)
+    }
+
+    fn stop_with(&mut self, reason: StopReason) {
+        self.pause();
+        self.stop = Some(reason);
+    }
+
+    // Breakpoints and watchpoints on the instruction about to execute
+    fn check_stop(&self, chip8: &Chip8) -> Result<Option<StopReason>, CpuError> {
+        let pc = chip8.program_counter;
+        let hit = self.breakpoints.iter().any(|breakpoint| {
+            breakpoint.addr == pc && breakpoint.condition.is_none_or(|c| c.holds(chip8))
+        });
+        if hit {
+            return Ok(Some(StopReason::Breakpoint { addr: pc }));
+        }
+        if self.watchpoints.is_empty() {
+            return Ok(None);
+        }
+        let opcode = chip8.memory_get_short(pc as usize)?;
+        let Some((access, range)) = memory_access(chip8, opcode) else {
+            return Ok(None);
+        };
+        for watchpoint in &self.watchpoints {
+            let start = range.start.max(watchpoint.range.start);
+            if watchpoint.access.matches(access) && start < range.end.min(watchpoint.range.end) {
+                return Ok(Some(StopReason::Watchpoint {
+                    addr: start,
+                    access,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn check_target(&mut self, chip8: &Chip8) -> Option<StopReason> {
+        match self.target? {
+            RunTarget::ReturnTo { pc, stack_pointer }
+                if chip8.program_counter == pc && chip8.stack_pointer == stack_pointer =>
+            {
+                Some(StopReason::Stepped)
+            }
+            RunTarget::Return { stack_pointer } if chip8.stack_pointer < stack_pointer => {
+                Some(StopReason::Returned)
+            }
+            _ => None,
+        }
+    }
+
+    // Carries out a command typed at the prompt, returning what to print
+    pub fn execute(&mut self, command: Command, chip8: &mut Chip8) -> Result<String, CpuError> {
+        let output = match command {
+            Command::Pause => {
+                self.pause();
+                state(chip8)
+            }
+            Command::Continue => {
+                self.resume(chip8);
+                String::new()
+            }
+            Command::Step => {
+                self.step(chip8)?;
+                state(chip8)
+            }
+            Command::StepOver => {
+                self.step_over(chip8)?;
+                if self.paused {
+                    state(chip8)
+                } else {
+                    String::new()
+                }
+            }
+            Command::Finish => {
+                if self.finish(chip8) {
+                    String::new()
+                } else {
+                    "not inside a subroutine".to_string()
+                }
+            }
+            Command::Break(breakpoint) => {
+                self.add_breakpoint(breakpoint);
+                format!("breakpoint {} set", self.breakpoints.len() - 1)
+            }
+            Command::Watch(watchpoint) => {
+                self.add_watchpoint(watchpoint);
+                format!(
+                    "watchpoint {} set",
+                    self.breakpoints.len() + self.watchpoints.len() - 1
+                )
+            }
+            Command::Delete(n) => {
+                if self.delete(n) {
+                    format!("deleted {}", n)
+                } else {
+                    format!("no breakpoint or watchpoint {}", n)
+                }
+            }
+            Command::List => self.list(),
+            Command::Registers => state(chip8),
+            Command::Help => DEBUGGER_HELP.to_string(),
+        };
+        Ok(output)
+    }
+
+    fn list(&self) -> String {
+        let mut lines = Vec::new();
+        for (n, breakpoint) in self.breakpoints.iter().enumerate() {
+            let mut line = format!("{}: break {:#05X}", n, breakpoint.addr);
+            if let Some(condition) = breakpoint.condition {
+                line += &format!(" if {}", condition);
+            }
+            lines.push(line);
+        }
+        for (n, watchpoint) in self.watchpoints.iter().enumerate() {
+            let access = match watchpoint.access {
+                Access::Read => "r",
+                Access::Write => "w",
+                Access::ReadWrite => "rw",
+            };
+            lines.push(format!(
+                "{}: watch {:#05X}-{:#05X} {}",
+                n + self.breakpoints.len(),
+                watchpoint.range.start,
+                watchpoint.range.end - 1,
+                access
+            ));
+        }
+        if lines.is_empty() {
+            return "no breakpoints or watchpoints".to_string();
+        }
+        lines.join("\n")
+    }
+}
+
+// The data memory an instruction is about to touch. Instruction fetches,
+// including the second word of F000 NNNN, don't count.
+fn memory_access(chip8: &Chip8, opcode: u16) -> Option<(Access, Range<usize>)> {
+    let i = chip8.i as usize;
+    let xo_chip = chip8.mode == Mode::XoChip;
+    match decode(opcode) {
+        Instruction::SaveRange { x, y } if xo_chip => {
+            Some((Access::Write, i..i + x.abs_diff(y) as usize + 1))
+        }
+        Instruction::LoadRange { x, y } if xo_chip => {
+            Some((Access::Read, i..i + x.abs_diff(y) as usize + 1))
+        }
+        Instruction::Audio if xo_chip => Some((Access::Read, i..i + AUDIO_PATTERN_SIZE)),
+        Instruction::StoreBcd(_) => Some((Access::Write, i..i + 3)),
+        Instruction::StoreRegisters(x) => Some((Access::Write, i..i + x as usize + 1)),
+        Instruction::LoadRegisters(x) => Some((Access::Read, i..i + x as usize + 1)),
+        Instruction::Draw { n, .. } => {
+            let len = if n == 0 { 32 } else { n as usize };
+            Some((
+                Access::Read,
+                i..i + len * chip8.screen.selected_plane_count(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+// Registers, stack, timers and the next instruction, for the prompt
+pub fn state(chip8: &Chip8) -> String {
+    let registers: Vec<String> = chip8
+        .v
+        .iter()
+        .enumerate()
+        .map(|(n, value)| format!("V{:X}={:02X}", n, value))
+        .collect();
+    let stack: Vec<String> = chip8.stack[..chip8.stack_pointer as usize]
+        .iter()
+        .map(|addr| format!("{:03X}", addr))
+        .collect();
+    let pc = chip8.program_counter;
+    let next = match chip8.memory_get_short(pc as usize) {
+        Ok(opcode) => format!("{:04X}  {}", opcode, decode(opcode)),
+        Err(err) => err.to_string(),
+    };
+    format!(
+        "PC={:03X} I={:03X} SP={} DT={:02X} ST={:02X}\n{}\n{}\nstack: [{}]\n{:03X}: {}",
+        pc,
+        chip8.i,
+        chip8.stack_pointer,
+        chip8.delay_timer,
+        chip8.sound_timer,
+        registers[..8].join(" "),
+        registers[8..].join(" "),
+        stack.join(" "),
+        pc,
+        next
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: 6005    LD V0, 05
+    // 202: 2208    CALL 208
+    // 204: 7001    ADD V0, 01
+    // 206: 1202    JP 202
+    // 208: A300    LD I, 300
+    // 20A: F033    LD B, V0
+    // 20C: 00EE    RET
+    const PROGRAM: [u8; 14] = [
+        0x60, 0x05, 0x22, 0x08, 0x70, 0x01, 0x12, 0x02, 0xA3, 0x00, 0xF0, 0x33, 0x00, 0xEE,
+    ];
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(&PROGRAM).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn test_paused_machine_does_not_run() {
+        let mut chip8 = machine();
+        chip8.delay_timer = 10;
+        let mut debugger = Debugger::new();
+        debugger.pause();
+        debugger.run_frame(&mut chip8, 10).unwrap();
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.delay_timer, 10);
+    }
+
+    #[test]
+    fn test_breakpoint_stops_and_resume_passes_it() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        debugger
+            .execute("b 0x204".parse().unwrap(), &mut chip8)
+            .unwrap();
+
+        debugger.run_frame(&mut chip8, 100).unwrap();
+        assert!(debugger.is_paused());
+        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(
+            debugger.take_stop(),
+            Some(StopReason::Breakpoint { addr: 0x204 })
+        );
+
+        debugger.resume(&chip8);
+        debugger.run_frame(&mut chip8, 100).unwrap();
+        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(chip8.v[0], 6);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        debugger
+            .execute("break 0x206 if V0 == 0x08".parse().unwrap(), &mut chip8)
+            .unwrap();
+        debugger.run_frame(&mut chip8, 100).unwrap();
+        assert_eq!(chip8.program_counter, 0x206);
+        assert_eq!(chip8.v[0], 8);
+    }
+
+    #[test]
+    fn test_step_over_and_finish() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        debugger.step(&mut chip8).unwrap();
+        assert_eq!(chip8.program_counter, 0x202);
+
+        debugger.step_over(&mut chip8).unwrap();
+        debugger.run_frame(&mut chip8, 100).unwrap();
+        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(chip8.stack_pointer, 0);
+        assert_eq!(debugger.take_stop(), Some(StopReason::Stepped));
+
+        debugger.step(&mut chip8).unwrap();
+        debugger.step(&mut chip8).unwrap();
+        debugger.step(&mut chip8).unwrap();
+        assert_eq!(chip8.program_counter, 0x208);
+        assert!(debugger.finish(&chip8));
+        debugger.run_frame(&mut chip8, 100).unwrap();
+        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(debugger.take_stop(), Some(StopReason::Returned));
+        assert!(!debugger.finish(&chip8));
+    }
+
+    #[test]
+    fn test_write_watchpoint() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        debugger
+            .execute("watch 0x301-0x302".parse().unwrap(), &mut chip8)
+            .unwrap();
+        debugger.run_frame(&mut chip8, 100).unwrap();
+        assert_eq!(chip8.program_counter, 0x20A);
+        assert_eq!(
+            debugger.take_stop(),
+            Some(StopReason::Watchpoint {
+                addr: 0x301,
+                access: Access::Write
+            })
+        );
+
+        // A read watchpoint on the same bytes doesn't fire
+        debugger.delete(0);
+        debugger.add_watchpoint(Watchpoint {
+            range: 0x300..0x303,
+            access: Access::Read,
+        });
+        debugger.resume(&chip8);
+        debugger.run_frame(&mut chip8, 20).unwrap();
+        assert!(!debugger.is_paused());
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            "b 0x2A4 if V3 == 0x10".parse(),
+            Ok(Command::Break(Breakpoint {
+                addr: 0x2A4,
+                condition: Some(Condition {
+                    operand: Operand::V(3),
+                    comparison: Comparison::Equal,
+                    value: 0x10,
+                }),
+            }))
+        );
+        assert_eq!(
+            "w $300 rw".parse(),
+            Ok(Command::Watch(Watchpoint {
+                range: 0x300..0x301,
+                access: Access::ReadWrite,
+            }))
+        );
+        assert!("b 0x200 if VG == 1".parse::<Command>().is_err());
+        assert!("jump".parse::<Command>().is_err());
+        let condition: Condition = "i>=768".parse().unwrap();
+        assert_eq!(condition.to_string(), "I >= 0x300");
+    }
+
+    #[test]
+    fn test_state_shows_next_instruction() {
+        let chip8 = machine();
+        let text = state(&chip8);
+        assert!(text.contains("PC=200"));
+        assert!(text.ends_with("200: 6005  LD V0, 0x05"));
+    }
+}
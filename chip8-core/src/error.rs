@@ -0,0 +1,39 @@
+use std::fmt;
+
+// Faults raised while executing an instruction. Once one is returned the
+// machine state is whatever it was right before the faulting instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    StackOverflow,
+    StackUnderflow,
+    MemoryOutOfBounds { addr: usize },
+    UnknownOpcode { pc: u16, opcode: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::StackOverflow => write!(f, "stack overflow"),
+            CpuError::StackUnderflow => write!(f, "stack underflow"),
+            CpuError::MemoryOutOfBounds { addr } => {
+                write!(f, "memory access out of bounds at {:#06X}", addr)
+            }
+            CpuError::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {:04X} at {:#05X}", opcode, pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+// What happened as a result of executing a single instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Continue,
+    // The program executed 00FD and wants the interpreter to stop
+    Exit,
+    // Blocked in Fx0A until a key is pressed and released. The program
+    // counter stays on the instruction, so stepping again keeps waiting.
+    WaitingForKey,
+}
@@ -0,0 +1,367 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chip::Chip8;
+use crate::debugger::parse_number;
+use crate::error::{CpuError, StepOutcome};
+use crate::keyboard::Chip8Key;
+use crate::movie::{Movie, MovieError};
+
+// Runs a machine frame by frame without a frontend, for CI and batch testing.
+// Frames are counted rather than timed, so a run is as fast as the host allows.
+
+// Where a run should end early, checked before every instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCondition {
+    // The program counter reaches an address
+    Pc(u16),
+    // The next instruction to execute is this opcode
+    Opcode(u16),
+}
+
+impl StopCondition {
+    fn reached(self, chip8: &Chip8) -> bool {
+        match self {
+            StopCondition::Pc(addr) => chip8.program_counter == addr,
+            StopCondition::Opcode(opcode) => {
+                chip8.memory_get_short(chip8.program_counter as usize) == Ok(opcode)
+            }
+        }
+    }
+}
+
+// `pc=0x2A4` or `opcode=0x00FD`
+impl FromStr for StopCondition {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.split_once('=') {
+            Some(("pc", addr)) => Ok(StopCondition::Pc(parse_number(addr)?)),
+            Some(("opcode", opcode)) => Ok(StopCondition::Opcode(parse_number(opcode)?)),
+            _ => Err(format!(
+                "invalid stop condition '{}', expected pc=<addr> or opcode=<opcode>",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for StopCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopCondition::Pc(addr) => write!(f, "pc={:#05X}", addr),
+            StopCondition::Opcode(opcode) => write!(f, "opcode={:04X}", opcode),
+        }
+    }
+}
+
+// A key held down from `frame` for `frames` frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    pub frame: u64,
+    pub key: Chip8Key,
+    pub frames: u64,
+}
+
+// Scripted input, written as `FRAME:KEY[:FRAMES]` entries separated by
+// whitespace or commas, e.g. `60:5 90:A:10`. A press lasts one frame unless
+// told otherwise. `#` starts a comment that runs to the end of the line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyScript {
+    presses: Vec<KeyPress>,
+}
+
+impl KeyScript {
+    pub fn presses(&self) -> &[KeyPress] {
+        &self.presses
+    }
+
+    // Presses and releases the keys scheduled for the start of `frame`
+    pub fn apply(&self, frame: u64, chip8: &mut Chip8) {
+        for press in &self.presses {
+            if frame == press.frame + press.frames {
+                chip8.keyboard.key_up(press.key);
+            }
+        }
+        for press in &self.presses {
+            if frame == press.frame {
+                chip8.keyboard.key_down(press.key);
+            }
+        }
+    }
+}
+
+impl FromStr for KeyScript {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut presses = Vec::new();
+        let entries = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|entry| !entry.is_empty());
+        for entry in entries {
+            let invalid = || format!("invalid key press '{}', expected FRAME:KEY[:FRAMES]", entry);
+            let mut fields = entry.split(':');
+            let frame = fields
+                .next()
+                .and_then(|f| f.parse().ok())
+                .ok_or_else(invalid)?;
+            let key = fields
+                .next()
+                .and_then(|k| u8::from_str_radix(k, 16).ok())
+                .and_then(Chip8Key::from_value)
+                .ok_or_else(invalid)?;
+            let frames = match fields.next() {
+                Some(n) => n.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?,
+                None => 1,
+            };
+            if fields.next().is_some() {
+                return Err(invalid());
+            }
+            presses.push(KeyPress { frame, key, frames });
+        }
+        Ok(KeyScript { presses })
+    }
+}
+
+// Why a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Finish {
+    // All the requested frames ran
+    Frames,
+    Reached(StopCondition),
+    // The program executed 00FD
+    Exit,
+    Fault(CpuError),
+}
+
+impl fmt::Display for Finish {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finish::Frames => write!(f, "ran all frames"),
+            Finish::Reached(condition) => write!(f, "reached {}", condition),
+            Finish::Exit => write!(f, "program exited"),
+            Finish::Fault(err) => write!(f, "fault: {}", err),
+        }
+    }
+}
+
+pub struct Headless {
+    pub chip8: Chip8,
+    pub instructions_per_frame: u32,
+    pub stop_at: Vec<StopCondition>,
+    pub keys: KeyScript,
+    // Sets the whole keypad each frame, after the key script
+    pub movie: Option<Movie>,
+    frame: u64,
+}
+
+impl Headless {
+    pub fn new(chip8: Chip8, instructions_per_frame: u32) -> Self {
+        Headless {
+            chip8,
+            instructions_per_frame,
+            stop_at: Vec::new(),
+            keys: KeyScript::default(),
+            movie: None,
+            frame: 0,
+        }
+    }
+
+    // Replays `movie` from power-on, at the speed it was recorded at
+    pub fn from_movie(movie: Movie, rom: &[u8]) -> Result<Self, MovieError> {
+        let mut headless = Headless::new(movie.machine(rom)?, movie.instructions_per_frame);
+        headless.movie = Some(movie);
+        Ok(headless)
+    }
+
+    // Frames completed so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Same as `Chip8::run_frame`, plus scripted keys and stop conditions.
+    // Returns why the run has to end, if it does.
+    pub fn run_frame(&mut self) -> Option<Finish> {
+        self.keys.apply(self.frame, &mut self.chip8);
+        if let Some(movie) = &self.movie {
+            movie.play(self.frame as usize, &mut self.chip8);
+        }
+        for _ in 0..self.instructions_per_frame {
+            let reached = self.stop_at.iter().find(|c| c.reached(&self.chip8));
+            if let Some(&condition) = reached {
+                return Some(Finish::Reached(condition));
+            }
+            match self.chip8.step() {
+                Ok(StepOutcome::Continue) => {}
+                Ok(StepOutcome::WaitingForKey) => break,
+                Ok(StepOutcome::Exit) => return Some(Finish::Exit),
+                Err(err) => return Some(Finish::Fault(err)),
+            }
+        }
+        self.chip8.tick_timers();
+        self.frame += 1;
+        None
+    }
+
+    pub fn run(&mut self, frames: u64) -> Finish {
+        self.run_with(frames, |_| {})
+    }
+
+    // Like `run`, calling `on_frame` after every frame that completes, e.g.
+    // to record video
+    pub fn run_with(&mut self, frames: u64, mut on_frame: impl FnMut(&Chip8)) -> Finish {
+        while self.frame < frames {
+            if let Some(finish) = self.run_frame() {
+                return finish;
+            }
+            on_frame(&self.chip8);
+        }
+        Finish::Frames
+    }
+}
+
+fn json_array<T: fmt::Display>(values: &[T]) -> String {
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    format!("[{}]", values.join(", "))
+}
+
+// Registers, timers, keypad and memory as a JSON object. Numbers are plain
+// decimal, memory is one hex string so that 64 KiB stays readable.
+pub fn state_json(chip8: &Chip8) -> String {
+    let memory: String = chip8.memory.iter().map(|b| format!("{:02x}", b)).collect();
+    let keys: Vec<bool> = Chip8Key::ALL
+        .iter()
+        .map(|&key| chip8.keyboard.is_key_down(key))
+        .collect();
+    let fields = [
+        ("mode", format!("\"{}\"", chip8.mode)),
+        ("pc", chip8.program_counter.to_string()),
+        ("i", chip8.i.to_string()),
+        ("v", json_array(&chip8.v)),
+        ("sp", chip8.stack_pointer.to_string()),
+        ("stack", json_array(&chip8.stack)),
+        ("delay_timer", chip8.delay_timer.to_string()),
+        ("sound_timer", chip8.sound_timer.to_string()),
+        ("hires", chip8.screen.is_hires().to_string()),
+        ("planes", chip8.screen.selected_planes().to_string()),
+        ("rpl", json_array(&chip8.rpl)),
+        ("pitch", chip8.pitch.to_string()),
+        ("audio_pattern", json_array(&chip8.audio_pattern)),
+        ("keys", json_array(&keys)),
+        ("memory", format!("\"{}\"", memory)),
+    ];
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("  \"{}\": {}", name, value))
+        .collect();
+    format!("{{\n{}\n}}\n", fields.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+    use crate::quirks::Quirks;
+    use crate::random::RngKind;
+
+    fn headless(program: &[u8]) -> Headless {
+        let mut chip8 = Chip8::new();
+        chip8.load(program).unwrap();
+        Headless::new(chip8, 10)
+    }
+
+    #[test]
+    fn test_runs_frames_and_ticks_timers() {
+        // LD V0, 0x3C; LD DT, V0; JP 0x204
+        let mut run = headless(&[0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04]);
+        assert_eq!(run.run(30), Finish::Frames);
+        assert_eq!(run.frame(), 30);
+        assert_eq!(run.chip8.delay_timer, 30);
+    }
+
+    #[test]
+    fn test_replays_a_movie() {
+        // V0 := key, wait for a key press; V1 += V0; loop
+        let rom = [0xF0, 0x0A, 0x81, 0x04, 0x12, 0x00];
+        let mut movie = Movie::new(
+            &rom,
+            Mode::Chip8,
+            Quirks::default(),
+            RngKind::SplitMix,
+            1,
+            10,
+        );
+        let mut chip8 = movie.machine(&rom).unwrap();
+        for frame in 0..40 {
+            // 3 tapped every ten frames
+            chip8
+                .keyboard
+                .set_mask(if frame % 10 < 5 { 1 << 3 } else { 0 });
+            movie.record(&chip8.keyboard);
+            chip8.run_frame(10).unwrap();
+        }
+        assert_eq!(chip8.v[1], 12);
+
+        let mut run = Headless::from_movie(movie, &rom).unwrap();
+        assert_eq!(run.run(40), Finish::Frames);
+        assert_eq!(run.chip8.snapshot(), chip8.snapshot());
+    }
+
+    #[test]
+    fn test_stops_at_pc_or_opcode_and_reports_faults() {
+        // ADD V0, 1; JP 0x200 on the tenth pass: SE V0, 10 skips into 00FD
+        let program = [0x70, 0x01, 0x30, 0x0A, 0x12, 0x00, 0x00, 0xFD, 0x00, 0x00];
+        let mut run = headless(&program);
+        run.stop_at.push("pc=0x206".parse().unwrap());
+        assert_eq!(run.run(100), Finish::Reached(StopCondition::Pc(0x206)));
+        assert_eq!(run.chip8.v[0], 10);
+
+        let mut run = headless(&program);
+        run.stop_at.push("opcode=0x00FD".parse().unwrap());
+        assert_eq!(run.run(100), Finish::Reached(StopCondition::Opcode(0x00FD)));
+
+        let mut run = headless(&program);
+        assert_eq!(run.run(100), Finish::Exit);
+
+        let mut run = headless(&[0x00, 0xEE]);
+        assert_eq!(run.run(100), Finish::Fault(CpuError::StackUnderflow));
+    }
+
+    #[test]
+    fn test_key_script_drives_fx0a() {
+        let script: KeyScript = "# start\n5:a:3, 20:3".parse().unwrap();
+        assert_eq!(
+            script.presses()[0],
+            KeyPress {
+                frame: 5,
+                key: Chip8Key::KeyA,
+                frames: 3
+            }
+        );
+        // LD V0, K; then spin
+        let mut run = headless(&[0xF0, 0x0A, 0x12, 0x02]);
+        run.keys = script;
+        run.run(8);
+        assert_eq!(run.chip8.v[0], 0);
+        run.run(9);
+        assert_eq!(run.chip8.v[0], 0xA);
+
+        assert!("5".parse::<KeyScript>().is_err());
+        assert!("5:G".parse::<KeyScript>().is_err());
+        assert!("5:1:0".parse::<KeyScript>().is_err());
+    }
+
+    #[test]
+    fn test_state_json() {
+        let mut chip8 = Chip8::new();
+        chip8.v[3] = 7;
+        let json = state_json(&chip8);
+        assert!(json.starts_with("{\n  \"mode\": \"chip8\",\n  \"pc\": 0,\n"));
+        assert!(json.contains("\"v\": [0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],"));
+        assert!(json.contains("\"memory\": \"f0909090f0"));
+        assert!(json.ends_with("\"\n}\n"));
+    }
+}
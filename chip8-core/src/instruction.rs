@@ -0,0 +1,529 @@
+use std::fmt;
+
+// One decoded opcode. `x` and `y` are register numbers, `kk` an immediate
+// byte, `n` a nibble and bare u16s are 12 bit addresses. XO-CHIP only
+// instructions decode in any mode, it's up to the interpreter to reject them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    // 00E0 - CLS
+    Clear,
+    // 00EE - RET
+    Return,
+    // 00CN - SCD n (SUPER-CHIP)
+    ScrollDown(u8),
+    // 00DN - SCU n (XO-CHIP)
+    ScrollUp(u8),
+    // 00FB - SCR (SUPER-CHIP)
+    ScrollRight,
+    // 00FC - SCL (SUPER-CHIP)
+    ScrollLeft,
+    // 00FD - EXIT (SUPER-CHIP)
+    Exit,
+    // 00FE - LOW (SUPER-CHIP)
+    LowRes,
+    // 00FF - HIGH (SUPER-CHIP)
+    HighRes,
+    // 1nnn - JP addr
+    Jump(u16),
+    // 2nnn - CALL addr
+    Call(u16),
+    // 3xkk - SE Vx, byte
+    SkipEqualByte { x: u8, kk: u8 },
+    // 4xkk - SNE Vx, byte
+    SkipNotEqualByte { x: u8, kk: u8 },
+    // 5xy0 - SE Vx, Vy
+    SkipEqual { x: u8, y: u8 },
+    // 5xy2 - SAVE Vx - Vy (XO-CHIP)
+    SaveRange { x: u8, y: u8 },
+    // 5xy3 - LOAD Vx - Vy (XO-CHIP)
+    LoadRange { x: u8, y: u8 },
+    // 6xkk - LD Vx, byte
+    LoadByte { x: u8, kk: u8 },
+    // 7xkk - ADD Vx, byte
+    AddByte { x: u8, kk: u8 },
+    // 8xy0 - LD Vx, Vy
+    Load { x: u8, y: u8 },
+    // 8xy1 - OR Vx, Vy
+    Or { x: u8, y: u8 },
+    // 8xy2 - AND Vx, Vy
+    And { x: u8, y: u8 },
+    // 8xy3 - XOR Vx, Vy
+    Xor { x: u8, y: u8 },
+    // 8xy4 - ADD Vx, Vy
+    Add { x: u8, y: u8 },
+    // 8xy5 - SUB Vx, Vy
+    Sub { x: u8, y: u8 },
+    // 8xy6 - SHR Vx, Vy
+    ShiftRight { x: u8, y: u8 },
+    // 8xy7 - SUBN Vx, Vy
+    SubNegated { x: u8, y: u8 },
+    // 8xyE - SHL Vx, Vy
+    ShiftLeft { x: u8, y: u8 },
+    // 9xy0 - SNE Vx, Vy
+    SkipNotEqual { x: u8, y: u8 },
+    // Annn - LD I, addr
+    LoadI(u16),
+    // Bnnn - JP V0, addr
+    JumpOffset(u16),
+    // Cxkk - RND Vx, byte
+    Random { x: u8, kk: u8 },
+    // Dxyn - DRW Vx, Vy, nibble. n == 0 draws a 16x16 sprite (SUPER-CHIP)
+    Draw { x: u8, y: u8, n: u8 },
+    // Ex9E - SKP Vx
+    SkipKeyPressed(u8),
+    // ExA1 - SKNP Vx
+    SkipKeyNotPressed(u8),
+    // F000 nnnn - LD I, LONG nnnn (XO-CHIP). The address is the next word.
+    LoadLongI,
+    // Fn01 - PLANE n (XO-CHIP)
+    Plane(u8),
+    // F002 - AUDIO (XO-CHIP)
+    Audio,
+    // Fx07 - LD Vx, DT
+    LoadDelay(u8),
+    // Fx0A - LD Vx, K
+    WaitKey(u8),
+    // Fx15 - LD DT, Vx
+    SetDelay(u8),
+    // Fx18 - LD ST, Vx
+    SetSound(u8),
+    // Fx1E - ADD I, Vx
+    AddI(u8),
+    // Fx29 - LD F, Vx
+    LoadFont(u8),
+    // Fx30 - LD HF, Vx (SUPER-CHIP)
+    LoadBigFont(u8),
+    // Fx33 - LD B, Vx
+    StoreBcd(u8),
+    // Fx3A - PITCH Vx (XO-CHIP)
+    Pitch(u8),
+    // Fx55 - LD [I], Vx
+    StoreRegisters(u8),
+    // Fx65 - LD Vx, [I]
+    LoadRegisters(u8),
+    // Fx75 - LD R, Vx (SUPER-CHIP)
+    StoreFlags(u8),
+    // Fx85 - LD Vx, R (SUPER-CHIP)
+    LoadFlags(u8),
+    // Anything else, kept as the raw word
+    Unknown(u16),
+}
+
+pub fn decode(opcode: u16) -> Instruction {
+    use Instruction::*;
+
+    let nnn = opcode & 0x0FFF;
+    let x = ((opcode >> 8) & 0x000F) as u8;
+    let y = ((opcode >> 4) & 0x000F) as u8;
+    let kk = (opcode & 0x00FF) as u8;
+    let n = (opcode & 0x000F) as u8;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => Clear,
+            0x00EE => Return,
+            0x00C0..=0x00CF => ScrollDown(n),
+            0x00D0..=0x00DF => ScrollUp(n),
+            0x00FB => ScrollRight,
+            0x00FC => ScrollLeft,
+            0x00FD => Exit,
+            0x00FE => LowRes,
+            0x00FF => HighRes,
+            _ => Unknown(opcode),
+        },
+        0x1000 => Jump(nnn),
+        0x2000 => Call(nnn),
+        0x3000 => SkipEqualByte { x, kk },
+        0x4000 => SkipNotEqualByte { x, kk },
+        0x5000 => match n {
+            0x0 => SkipEqual { x, y },
+            0x2 => SaveRange { x, y },
+            0x3 => LoadRange { x, y },
+            _ => Unknown(opcode),
+        },
+        0x6000 => LoadByte { x, kk },
+        0x7000 => AddByte { x, kk },
+        0x8000 => match n {
+            0x0 => Load { x, y },
+            0x1 => Or { x, y },
+            0x2 => And { x, y },
+            0x3 => Xor { x, y },
+            0x4 => Add { x, y },
+            0x5 => Sub { x, y },
+            0x6 => ShiftRight { x, y },
+            0x7 => SubNegated { x, y },
+            0xE => ShiftLeft { x, y },
+            _ => Unknown(opcode),
+        },
+        0x9000 if n == 0 => SkipNotEqual { x, y },
+        0xA000 => LoadI(nnn),
+        0xB000 => JumpOffset(nnn),
+        0xC000 => Random { x, kk },
+        0xD000 => Draw { x, y, n },
+        0xE000 => match kk {
+            0x9E => SkipKeyPressed(x),
+            0xA1 => SkipKeyNotPressed(x),
+            _ => Unknown(opcode),
+        },
+        0xF000 => match kk {
+            0x00 if x == 0 => LoadLongI,
+            0x01 => Plane(x),
+            0x02 if x == 0 => Audio,
+            0x07 => LoadDelay(x),
+            0x0A => WaitKey(x),
+            0x15 => SetDelay(x),
+            0x18 => SetSound(x),
+            0x1E => AddI(x),
+            0x29 => LoadFont(x),
+            0x30 => LoadBigFont(x),
+            0x33 => StoreBcd(x),
+            0x3A => Pitch(x),
+            0x55 => StoreRegisters(x),
+            0x65 => LoadRegisters(x),
+            0x75 => StoreFlags(x),
+            0x85 => LoadFlags(x),
+            _ => Unknown(opcode),
+        },
+        _ => Unknown(opcode),
+    }
+}
+
+impl Instruction {
+    // The opcode this decodes from, so that decode(op).encode() == op
+    pub fn encode(self) -> u16 {
+        use Instruction::*;
+
+        let xy = |base: u16, x: u8, y: u8| base | (x as u16) << 8 | (y as u16) << 4;
+        let xkk = |base: u16, x: u8, kk: u8| base | (x as u16) << 8 | kk as u16;
+        let fx = |x: u8, low: u16| 0xF000 | (x as u16) << 8 | low;
+        match self {
+            Clear => 0x00E0,
+            Return => 0x00EE,
+            ScrollDown(n) => 0x00C0 | n as u16,
+            ScrollUp(n) => 0x00D0 | n as u16,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            LowRes => 0x00FE,
+            HighRes => 0x00FF,
+            Jump(nnn) => 0x1000 | nnn,
+            Call(nnn) => 0x2000 | nnn,
+            SkipEqualByte { x, kk } => xkk(0x3000, x, kk),
+            SkipNotEqualByte { x, kk } => xkk(0x4000, x, kk),
+            SkipEqual { x, y } => xy(0x5000, x, y),
+            SaveRange { x, y } => xy(0x5002, x, y),
+            LoadRange { x, y } => xy(0x5003, x, y),
+            LoadByte { x, kk } => xkk(0x6000, x, kk),
+            AddByte { x, kk } => xkk(0x7000, x, kk),
+            Load { x, y } => xy(0x8000, x, y),
+            Or { x, y } => xy(0x8001, x, y),
+            And { x, y } => xy(0x8002, x, y),
+            Xor { x, y } => xy(0x8003, x, y),
+            Add { x, y } => xy(0x8004, x, y),
+            Sub { x, y } => xy(0x8005, x, y),
+            ShiftRight { x, y } => xy(0x8006, x, y),
+            SubNegated { x, y } => xy(0x8007, x, y),
+            ShiftLeft { x, y } => xy(0x800E, x, y),
+            SkipNotEqual { x, y } => xy(0x9000, x, y),
+            LoadI(nnn) => 0xA000 | nnn,
+            JumpOffset(nnn) => 0xB000 | nnn,
+            Random { x, kk } => xkk(0xC000, x, kk),
+            Draw { x, y, n } => xy(0xD000, x, y) | n as u16,
+            SkipKeyPressed(x) => xkk(0xE000, x, 0x9E),
+            SkipKeyNotPressed(x) => xkk(0xE000, x, 0xA1),
+            LoadLongI => 0xF000,
+            Plane(n) => fx(n, 0x01),
+            Audio => 0xF002,
+            LoadDelay(x) => fx(x, 0x07),
+            WaitKey(x) => fx(x, 0x0A),
+            SetDelay(x) => fx(x, 0x15),
+            SetSound(x) => fx(x, 0x18),
+            AddI(x) => fx(x, 0x1E),
+            LoadFont(x) => fx(x, 0x29),
+            LoadBigFont(x) => fx(x, 0x30),
+            StoreBcd(x) => fx(x, 0x33),
+            Pitch(x) => fx(x, 0x3A),
+            StoreRegisters(x) => fx(x, 0x55),
+            LoadRegisters(x) => fx(x, 0x65),
+            StoreFlags(x) => fx(x, 0x75),
+            LoadFlags(x) => fx(x, 0x85),
+            Unknown(opcode) => opcode,
+        }
+    }
+
+    // Instructions that only exist on XO-CHIP
+    pub fn is_xo_chip(self) -> bool {
+        use Instruction::*;
+
+        matches!(
+            self,
+            ScrollUp(_)
+                | SaveRange { .. }
+                | LoadRange { .. }
+                | LoadLongI
+                | Plane(_)
+                | Audio
+                | Pitch(_)
+        )
+    }
+
+    // Octo syntax, which reads the skips as the condition that *runs* the
+    // next instruction: 3xkk is `if vx != kk then`
+    pub fn octo(self) -> Octo {
+        Octo(self)
+    }
+}
+
+// Mnemonics as in Cowgod's reference, plus the usual SUPER-CHIP and XO-CHIP
+// ones. Bytes and addresses are printed in hex, nibbles in decimal.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Clear => write!(f, "CLS"),
+            Return => write!(f, "RET"),
+            ScrollDown(n) => write!(f, "SCD {}", n),
+            ScrollUp(n) => write!(f, "SCU {}", n),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            LowRes => write!(f, "LOW"),
+            HighRes => write!(f, "HIGH"),
+            Jump(nnn) => write!(f, "JP {:#05X}", nnn),
+            Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            SkipEqualByte { x, kk } => write!(f, "SE V{:X}, {:#04X}", x, kk),
+            SkipNotEqualByte { x, kk } => write!(f, "SNE V{:X}, {:#04X}", x, kk),
+            SkipEqual { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            SaveRange { x, y } => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            LoadRange { x, y } => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            LoadByte { x, kk } => write!(f, "LD V{:X}, {:#04X}", x, kk),
+            AddByte { x, kk } => write!(f, "ADD V{:X}, {:#04X}", x, kk),
+            Load { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubNegated { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipNotEqual { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LoadI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            JumpOffset(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Random { x, kk } => write!(f, "RND V{:X}, {:#04X}", x, kk),
+            Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipKeyPressed(x) => write!(f, "SKP V{:X}", x),
+            SkipKeyNotPressed(x) => write!(f, "SKNP V{:X}", x),
+            LoadLongI => write!(f, "LD I, LONG"),
+            Plane(n) => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+            WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            AddI(x) => write!(f, "ADD I, V{:X}", x),
+            LoadFont(x) => write!(f, "LD F, V{:X}", x),
+            LoadBigFont(x) => write!(f, "LD HF, V{:X}", x),
+            StoreBcd(x) => write!(f, "LD B, V{:X}", x),
+            Pitch(x) => write!(f, "PITCH V{:X}", x),
+            StoreRegisters(x) => write!(f, "LD [I], V{:X}", x),
+            LoadRegisters(x) => write!(f, "LD V{:X}, [I]", x),
+            StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            Unknown(opcode) => write!(f, "DW {:#06X}", opcode),
+        }
+    }
+}
+
+pub struct Octo(Instruction);
+
+impl fmt::Display for Octo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+
+        match self.0 {
+            Clear => write!(f, "clear"),
+            Return => write!(f, "return"),
+            ScrollDown(n) => write!(f, "scroll-down {}", n),
+            ScrollUp(n) => write!(f, "scroll-up {}", n),
+            ScrollRight => write!(f, "scroll-right"),
+            ScrollLeft => write!(f, "scroll-left"),
+            Exit => write!(f, "exit"),
+            LowRes => write!(f, "lores"),
+            HighRes => write!(f, "hires"),
+            Jump(nnn) => write!(f, "jump {:#05X}", nnn),
+            Call(nnn) => write!(f, ":call {:#05X}", nnn),
+            SkipEqualByte { x, kk } => write!(f, "if v{:x} != {:#04X} then", x, kk),
+            SkipNotEqualByte { x, kk } => write!(f, "if v{:x} == {:#04X} then", x, kk),
+            SkipEqual { x, y } => write!(f, "if v{:x} != v{:x} then", x, y),
+            SaveRange { x, y } => write!(f, "save v{:x} - v{:x}", x, y),
+            LoadRange { x, y } => write!(f, "load v{:x} - v{:x}", x, y),
+            LoadByte { x, kk } => write!(f, "v{:x} := {:#04X}", x, kk),
+            AddByte { x, kk } => write!(f, "v{:x} += {:#04X}", x, kk),
+            Load { x, y } => write!(f, "v{:x} := v{:x}", x, y),
+            Or { x, y } => write!(f, "v{:x} |= v{:x}", x, y),
+            And { x, y } => write!(f, "v{:x} &= v{:x}", x, y),
+            Xor { x, y } => write!(f, "v{:x} ^= v{:x}", x, y),
+            Add { x, y } => write!(f, "v{:x} += v{:x}", x, y),
+            Sub { x, y } => write!(f, "v{:x} -= v{:x}", x, y),
+            ShiftRight { x, y } => write!(f, "v{:x} >>= v{:x}", x, y),
+            SubNegated { x, y } => write!(f, "v{:x} =- v{:x}", x, y),
+            ShiftLeft { x, y } => write!(f, "v{:x} <<= v{:x}", x, y),
+            SkipNotEqual { x, y } => write!(f, "if v{:x} == v{:x} then", x, y),
+            LoadI(nnn) => write!(f, "i := {:#05X}", nnn),
+            JumpOffset(nnn) => write!(f, "jump0 {:#05X}", nnn),
+            Random { x, kk } => write!(f, "v{:x} := random {:#04X}", x, kk),
+            Draw { x, y, n } => write!(f, "sprite v{:x} v{:x} {}", x, y, n),
+            SkipKeyPressed(x) => write!(f, "if v{:x} -key then", x),
+            SkipKeyNotPressed(x) => write!(f, "if v{:x} key then", x),
+            LoadLongI => write!(f, "i := long"),
+            Plane(n) => write!(f, "plane {}", n),
+            Audio => write!(f, "audio"),
+            LoadDelay(x) => write!(f, "v{:x} := delay", x),
+            WaitKey(x) => write!(f, "v{:x} := key", x),
+            SetDelay(x) => write!(f, "delay := v{:x}", x),
+            SetSound(x) => write!(f, "buzzer := v{:x}", x),
+            AddI(x) => write!(f, "i += v{:x}", x),
+            LoadFont(x) => write!(f, "i := hex v{:x}", x),
+            LoadBigFont(x) => write!(f, "i := bighex v{:x}", x),
+            StoreBcd(x) => write!(f, "bcd v{:x}", x),
+            Pitch(x) => write!(f, "pitch := v{:x}", x),
+            StoreRegisters(x) => write!(f, "save v{:x}", x),
+            LoadRegisters(x) => write!(f, "load v{:x}", x),
+            StoreFlags(x) => write!(f, "saveflags v{:x}", x),
+            LoadFlags(x) => write!(f, "loadflags v{:x}", x),
+            Unknown(opcode) => write!(f, "{:#04X} {:#04X}", opcode >> 8, opcode & 0xFF),
+        }
+    }
+}
+
+// One line of a listing: the instruction at `addr` and how many bytes it
+// takes. F000 NNNN is four bytes long, anything else two, and a lone byte at
+// the end of the program is data.
+pub struct Disassembly {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub instruction: Option<Instruction>,
+}
+
+impl Disassembly {
+    // The word after F000, which is the address it loads
+    fn long_address(&self) -> Option<u16> {
+        match (self.instruction, self.bytes.as_slice()) {
+            (Some(Instruction::LoadLongI), [_, _, high, low]) => {
+                Some(u16::from_be_bytes([*high, *low]))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(&self) -> String {
+        match (self.instruction, self.long_address()) {
+            (Some(instruction), Some(addr)) => format!("{} {:#06X}", instruction, addr),
+            (Some(instruction), None) => instruction.to_string(),
+            (None, _) => format!("DB {:#04X}", self.bytes[0]),
+        }
+    }
+
+    pub fn octo(&self) -> String {
+        match (self.instruction, self.long_address()) {
+            (Some(instruction), Some(addr)) => format!("{} {:#06X}", instruction.octo(), addr),
+            (Some(instruction), None) => instruction.octo().to_string(),
+            (None, _) => format!("{:#04X}", self.bytes[0]),
+        }
+    }
+}
+
+// Linear sweep over a program loaded at `origin`. Data mixed in with the
+// code comes out as whatever instructions it happens to decode to.
+pub fn disassemble(program: &[u8], origin: u16, xo_chip: bool) -> Vec<Disassembly> {
+    let mut listing = Vec::new();
+    let mut pos = 0;
+    while pos < program.len() {
+        let addr = origin.wrapping_add(pos as u16);
+        let Some(word) = program.get(pos..pos + 2) else {
+            listing.push(Disassembly {
+                addr,
+                bytes: program[pos..].to_vec(),
+                instruction: None,
+            });
+            break;
+        };
+        let mut instruction = decode(u16::from_be_bytes([word[0], word[1]]));
+        let mut len = 2;
+        if instruction == Instruction::LoadLongI {
+            if xo_chip && pos + 4 <= program.len() {
+                len = 4;
+            } else {
+                // Without the word after it, F000 means nothing
+                instruction = Instruction::Unknown(0xF000);
+            }
+        }
+        listing.push(Disassembly {
+            addr,
+            bytes: program[pos..pos + len].to_vec(),
+            instruction: Some(instruction),
+        });
+        pos += len;
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_encode_round_trip() {
+        for opcode in 0..=u16::MAX {
+            assert_eq!(decode(opcode).encode(), opcode, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_decode_examples() {
+        assert_eq!(decode(0xD125), Instruction::Draw { x: 1, y: 2, n: 5 });
+        assert_eq!(decode(0x22A4), Instruction::Call(0x2A4));
+        assert_eq!(decode(0x8AB6), Instruction::ShiftRight { x: 0xA, y: 0xB });
+        assert_eq!(decode(0x5121), Instruction::Unknown(0x5121));
+        assert_eq!(decode(0xF100), Instruction::Unknown(0xF100));
+        assert!(decode(0xF301).is_xo_chip());
+        assert!(!decode(0xF330).is_xo_chip());
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        assert_eq!(decode(0xD125).to_string(), "DRW V1, V2, 5");
+        assert_eq!(decode(0x6A0F).to_string(), "LD VA, 0x0F");
+        assert_eq!(decode(0x12A4).to_string(), "JP 0x2A4");
+        assert_eq!(decode(0xFB55).to_string(), "LD [I], VB");
+        assert_eq!(decode(0x0123).to_string(), "DW 0x0123");
+        assert_eq!(decode(0x3A0F).octo().to_string(), "if va != 0x0F then");
+        assert_eq!(decode(0xD125).octo().to_string(), "sprite v1 v2 5");
+    }
+
+    #[test]
+    fn test_disassemble_listing() {
+        let program = [0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0, 0xAB];
+        let listing = disassemble(&program, 0x200, true);
+        let lines: Vec<(u16, String)> = listing
+            .iter()
+            .map(|line| (line.addr, line.mnemonic()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (0x200, "LD I, LONG 0x1234".to_string()),
+                (0x204, "CLS".to_string()),
+                (0x206, "DB 0xAB".to_string()),
+            ]
+        );
+        assert_eq!(listing[0].octo(), "i := long 0x1234");
+
+        // Outside XO-CHIP mode F000 doesn't swallow the word after it
+        let listing = disassemble(&program, 0x200, false);
+        assert_eq!(listing[0].mnemonic(), "DW 0xF000");
+        assert_eq!(listing[1].mnemonic(), "JP 0x234");
+    }
+}
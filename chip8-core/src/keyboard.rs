@@ -0,0 +1,119 @@
+pub const TOTAL_KEYS: usize = 16;
+
+// Keys are declared in hex order, so `key as usize` is the value programs see
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Key {
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    KeyA,
+    KeyB,
+    KeyC,
+    KeyD,
+    KeyE,
+    KeyF,
+}
+
+impl Chip8Key {
+    pub const ALL: [Chip8Key; TOTAL_KEYS] = [
+        Chip8Key::Key0,
+        Chip8Key::Key1,
+        Chip8Key::Key2,
+        Chip8Key::Key3,
+        Chip8Key::Key4,
+        Chip8Key::Key5,
+        Chip8Key::Key6,
+        Chip8Key::Key7,
+        Chip8Key::Key8,
+        Chip8Key::Key9,
+        Chip8Key::KeyA,
+        Chip8Key::KeyB,
+        Chip8Key::KeyC,
+        Chip8Key::KeyD,
+        Chip8Key::KeyE,
+        Chip8Key::KeyF,
+    ];
+
+    // The hex digit printed on the key, which is what programs see
+    pub fn value(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_value(value: u8) -> Option<Chip8Key> {
+        Chip8Key::ALL.get(value as usize).copied()
+    }
+}
+
+pub struct Keyboard {
+    pub(crate) keys: [bool; TOTAL_KEYS],
+    pub last_key: Option<Chip8Key>,
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Keyboard {
+            keys: [false; TOTAL_KEYS],
+            last_key: None,
+        }
+    }
+
+    pub fn key_down(&mut self, key: Chip8Key) {
+        self.keys[key as usize] = true;
+        self.last_key = Some(key);
+    }
+
+    pub fn key_up(&mut self, key: Chip8Key) {
+        self.keys[key as usize] = false;
+        self.last_key = None;
+    }
+
+    pub fn is_key_down(&self, key: Chip8Key) -> bool {
+        self.keys[key as usize]
+    }
+
+    pub fn first_pressed(&self) -> Option<Chip8Key> {
+        Chip8Key::ALL.into_iter().find(|&key| self.is_key_down(key))
+    }
+
+    // Raw access by hex key value, for frontends that don't want to go through Chip8Key
+    pub fn set_key_index(&mut self, index: usize, pressed: bool) -> Result<(), &'static str> {
+        match self.keys.get_mut(index) {
+            Some(key) => {
+                *key = pressed;
+                Ok(())
+            }
+            None => Err("key index out of bounds!"),
+        }
+    }
+
+    pub fn is_key_index_down(&self, index: usize) -> bool {
+        self.keys.get(index).copied().unwrap_or(false)
+    }
+
+    // The whole keypad as a bitmask, bit n set while key n is down
+    pub fn mask(&self) -> u16 {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (n, &down)| mask | ((down as u16) << n))
+    }
+
+    pub fn set_mask(&mut self, mask: u16) {
+        for (n, key) in self.keys.iter_mut().enumerate() {
+            *key = mask & (1 << n) != 0;
+        }
+    }
+}
@@ -0,0 +1,36 @@
+pub mod assembler;
+pub mod audio;
+pub mod checksum;
+pub mod chip;
+pub mod debugger;
+pub mod error;
+pub mod headless;
+pub mod instruction;
+pub mod keyboard;
+pub mod mode;
+pub mod movie;
+pub mod octo;
+pub mod palette;
+pub mod png;
+pub mod quirks;
+pub mod random;
+#[cfg(test)]
+mod reference;
+pub mod rewind;
+pub mod screen;
+pub mod snapshot;
+pub mod video;
+
+pub use audio::{ToneGenerator, Waveform};
+pub use chip::Chip8;
+pub use debugger::Debugger;
+pub use error::{CpuError, StepOutcome};
+pub use instruction::{decode, Instruction};
+pub use keyboard::{Chip8Key, Keyboard};
+pub use mode::Mode;
+pub use movie::{Movie, MovieError};
+pub use palette::{Palette, Theme};
+pub use quirks::Quirks;
+pub use rewind::RewindBuffer;
+pub use screen::Screen;
+pub use snapshot::{Snapshot, SnapshotError};
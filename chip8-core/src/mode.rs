@@ -0,0 +1,48 @@
+use std::fmt;
+use std::str::FromStr;
+
+pub const CHIP8_MEMORY_CAPACITY: usize = 0x1000;
+pub const XO_CHIP_MEMORY_CAPACITY: usize = 0x10000;
+
+// Which machine is being emulated. SUPER-CHIP instructions are always
+// available, XO-CHIP additionally needs the bigger address space and the
+// instructions that are ambiguous with plain CHIP-8 ones (F000 NNNN).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Chip8,
+    XoChip,
+}
+
+impl Mode {
+    pub fn memory_capacity(self) -> usize {
+        match self {
+            Mode::Chip8 => CHIP8_MEMORY_CAPACITY,
+            Mode::XoChip => XO_CHIP_MEMORY_CAPACITY,
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "chip8" => Ok(Mode::Chip8),
+            "xochip" => Ok(Mode::XoChip),
+            _ => Err(format!(
+                "unknown mode '{}', expected one of: chip8, xochip",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Chip8 => write!(f, "chip8"),
+            Mode::XoChip => write!(f, "xochip"),
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt;
+
+use crate::checksum::crc32;
+use crate::chip::Chip8;
+use crate::keyboard::Keyboard;
+use crate::mode::Mode;
+use crate::quirks::Quirks;
+use crate::random::RngKind;
+
+// On-disk layout, all integers little endian:
+//
+//   magic "C8MV", u16 version
+//   u32 CRC-32 of the ROM
+//   u8 mode, u8 quirk bits, u8 RNG kind (0 SplitMix, 1 was a since removed
+//   VIP-style generator), u64 seed
+//   u32 instructions per frame
+//   u32 frame count, then a u16 keypad bitmask per frame, bit n for key n
+//   u32 CRC-32 of everything before it
+pub const MOVIE_MAGIC: &[u8; 4] = b"C8MV";
+pub const MOVIE_VERSION: u16 = 1;
+const HEADER_LEN: usize = 4 + 2 + 4 + 3 + 8 + 4 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Truncated,
+    Invalid(&'static str),
+    // The ROM being played isn't the one the movie was recorded with
+    WrongRom { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a CHIP-8 movie"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "unsupported movie version {}", version)
+            }
+            MovieError::ChecksumMismatch => write!(f, "movie is corrupted (bad checksum)"),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::Invalid(what) => write!(f, "invalid movie: {}", what),
+            MovieError::WrongRom { expected, actual } => write!(
+                f,
+                "movie was recorded with a different ROM (CRC-32 {:08x}, this one is {:08x})",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+// A run from power-on: everything that decides how the machine starts, then
+// the keypad for every frame. Replaying it on the same ROM repeats the run
+// exactly, since timers and Cxkk are deterministic given the seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_crc: u32,
+    pub mode: Mode,
+    pub quirks: Quirks,
+    pub rng: RngKind,
+    pub seed: u64,
+    pub instructions_per_frame: u32,
+    pub frames: Vec<u16>,
+}
+
+impl Movie {
+    pub fn new(
+        rom: &[u8],
+        mode: Mode,
+        quirks: Quirks,
+        rng: RngKind,
+        seed: u64,
+        instructions_per_frame: u32,
+    ) -> Movie {
+        Movie {
+            rom_crc: crc32(rom),
+            mode,
+            quirks,
+            rng,
+            seed,
+            instructions_per_frame,
+            frames: Vec::new(),
+        }
+    }
+
+    // A freshly powered-on machine with `rom` loaded, the state frame 0 starts from
+    pub fn machine(&self, rom: &[u8]) -> Result<Chip8, MovieError> {
+        let actual = crc32(rom);
+        if actual != self.rom_crc {
+            return Err(MovieError::WrongRom {
+                expected: self.rom_crc,
+                actual,
+            });
+        }
+        let mut chip8 = Chip8::with_quirks(self.quirks);
+        chip8.set_mode(self.mode);
+        chip8.rng = self.rng.with_seed(self.seed);
+        chip8
+            .load(rom)
+            .map_err(|_| MovieError::Invalid("ROM too large for the movie's mode"))?;
+        Ok(chip8)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // Call at the start of every frame, before it runs
+    pub fn record(&mut self, keyboard: &Keyboard) {
+        self.frames.push(keyboard.mask());
+    }
+
+    // Sets the keypad for `frame` before it runs. Past the end every key is
+    // released and this returns false.
+    pub fn play(&self, frame: usize, chip8: &mut Chip8) -> bool {
+        let mask = self.frames.get(frame).copied();
+        chip8.keyboard.set_mask(mask.unwrap_or(0));
+        mask.is_some()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.frames.len() * 2 + 4);
+        out.extend_from_slice(MOVIE_MAGIC);
+        out.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_crc.to_le_bytes());
+        out.push(match self.mode {
+            Mode::Chip8 => 0,
+            Mode::XoChip => 1,
+        });
+        out.push(self.quirks.to_bits());
+        out.push(match self.rng {
+            RngKind::SplitMix => 0,
+        });
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.extend_from_slice(&self.instructions_per_frame.to_le_bytes());
+        out.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for mask in &self.frames {
+            out.extend_from_slice(&mask.to_le_bytes());
+        }
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        if data.len() < MOVIE_MAGIC.len() + 2 {
+            return Err(MovieError::Truncated);
+        }
+        if &data[..4] != MOVIE_MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        if data.len() < HEADER_LEN + 4 {
+            return Err(MovieError::Truncated);
+        }
+        let (body, checksum) = data.split_at(data.len() - 4);
+        if crc32(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(MovieError::ChecksumMismatch);
+        }
+
+        let u32_at = |pos: usize| u32::from_le_bytes(body[pos..pos + 4].try_into().unwrap());
+        let rom_crc = u32_at(6);
+        let mode = match body[10] {
+            0 => Mode::Chip8,
+            1 => Mode::XoChip,
+            _ => return Err(MovieError::Invalid("unknown mode")),
+        };
+        let quirks = Quirks::from_bits(body[11]);
+        let rng = match body[12] {
+            0 => RngKind::SplitMix,
+            1 => {
+                return Err(MovieError::Invalid(
+                    "the VIP-style RNG is no longer supported",
+                ))
+            }
+            _ => return Err(MovieError::Invalid("unknown RNG")),
+        };
+        let seed = u64::from_le_bytes(body[13..21].try_into().unwrap());
+        let instructions_per_frame = u32_at(21);
+        let frame_count = u32_at(25) as usize;
+        let frames_data = &body[HEADER_LEN..];
+        if frames_data.len() != frame_count * 2 {
+            return Err(MovieError::Invalid("frame count doesn't match the data"));
+        }
+        let frames = frames_data
+            .chunks(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+
+        Ok(Movie {
+            rom_crc,
+            mode,
+            quirks,
+            rng,
+            seed,
+            instructions_per_frame,
+            frames,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::Chip8Key;
+
+    // Flashes random digits and moves right while key 5 is held, so both the
+    // keys and the RNG end up in the machine state
+    const ROM: [u8; 16] = [
+        0x63, 0x05, // V3 := 5
+        0xC2, 0x0F, // V2 := random 0F
+        0xF2, 0x29, // I := hex V2
+        0xD0, 0x15, // sprite V0 V1 5
+        0xE3, 0xA1, // if key V3 is up, skip
+        0x70, 0x01, // V0 += 1
+        0xD0, 0x15, // sprite V0 V1 5, erase
+        0x12, 0x02, // jump back to the random draw
+    ];
+
+    fn record() -> (Movie, Chip8) {
+        let mut movie = Movie::new(&ROM, Mode::Chip8, Quirks::vip(), RngKind::SplitMix, 7, 9);
+        let mut chip8 = movie.machine(&ROM).unwrap();
+        for frame in 0..120 {
+            if frame == 30 {
+                chip8.keyboard.key_down(Chip8Key::Key5);
+            }
+            if frame == 50 {
+                chip8.keyboard.key_up(Chip8Key::Key5);
+            }
+            movie.record(&chip8.keyboard);
+            chip8.run_frame(movie.instructions_per_frame).unwrap();
+        }
+        (movie, chip8)
+    }
+
+    #[test]
+    fn test_playback_reproduces_recording() {
+        let (movie, recorded) = record();
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        let mut chip8 = movie.machine(&ROM).unwrap();
+        let mut frame = 0;
+        while movie.play(frame, &mut chip8) {
+            chip8.run_frame(movie.instructions_per_frame).unwrap();
+            frame += 1;
+        }
+        assert_eq!(frame, 120);
+        assert_eq!(chip8.snapshot(), recorded.snapshot());
+    }
+
+    #[test]
+    fn test_keypad_masks() {
+        let (movie, _) = record();
+        assert_eq!(movie.frames[29], 0);
+        assert_eq!(movie.frames[30], 1 << 5);
+        assert_eq!(movie.frames[50], 0);
+    }
+
+    #[test]
+    fn test_rejects_other_rom_and_bad_files() {
+        let (movie, _) = record();
+        let mut other = ROM;
+        other[1] = 6;
+        assert!(matches!(
+            movie.machine(&other),
+            Err(MovieError::WrongRom { .. })
+        ));
+
+        let mut bytes = movie.to_bytes();
+        assert_eq!(
+            Movie::from_bytes(b"C8SS\x01\x00"),
+            Err(MovieError::BadMagic)
+        );
+        assert_eq!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MovieError::ChecksumMismatch)
+        );
+        bytes[4] = 9;
+        assert_eq!(
+            Movie::from_bytes(&bytes),
+            Err(MovieError::UnsupportedVersion(9))
+        );
+    }
+
+    #[test]
+    fn test_rejects_the_removed_vip_style_rng() {
+        let (movie, _) = record();
+        let mut bytes = movie.to_bytes();
+        bytes.truncate(bytes.len() - 4);
+        bytes[12] = 1;
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        assert_eq!(
+            Movie::from_bytes(&bytes),
+            Err(MovieError::Invalid(
+                "the VIP-style RNG is no longer supported"
+            ))
+        );
+    }
+}
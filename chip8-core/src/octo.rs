@@ -0,0 +1,1261 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+use crate::assembler::AsmError;
+
+// Compiles Octo (https://github.com/JohnEarnest/Octo) source into a ROM,
+// following the reference compiler's encodings: the same pseudo-ops for
+// `if vx < vy`, the jump to `main` at 0x200 that is dropped when `main`
+// comes first, and right-to-left `:calc` expressions without precedence.
+//
+// Supported: labels, `:const`, `:alias`, `:calc`, `:macro`, `:byte`,
+// `:pointer`, `:org`, `:next`, `:unpack`, `:call`, `:breakpoint`,
+// `:monitor`, `:assert`, `:stringmode`, `if ... then`,
+// `if ... begin ... else ... end`, `loop ... while ... again` and every
+// CHIP-8, SUPER-CHIP and XO-CHIP statement.
+//
+// Known gaps: strings can't span lines, and `:breakpoint` and `:monitor`
+// are read but do nothing since there's no Octo debugger to hand them to.
+
+const ORIGIN: usize = 0x200;
+const MEMORY_SIZE: usize = 0x10000;
+// Guards against macros that expand into themselves
+const MAX_EXPANSIONS: usize = 100_000;
+
+#[derive(Debug, Clone)]
+struct Token {
+    // As written, quotes included, so a quoted "}" is never a closing brace
+    text: String,
+    line: usize,
+    column: usize,
+    // The contents of a quoted string with its escapes resolved
+    string: Option<String>,
+}
+
+impl Token {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: None,
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+// Whitespace separated, `#` comments to the end of the line. A quoted
+// string is one token, whitespace and all.
+fn tokenize(source: &str) -> Result<VecDeque<Token>, AsmError> {
+    let mut tokens = VecDeque::new();
+    for (n, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut pos = 0;
+        while pos < chars.len() {
+            if chars[pos] == '#' {
+                break;
+            }
+            if chars[pos].is_whitespace() {
+                pos += 1;
+                continue;
+            }
+            let mut token = Token {
+                text: String::new(),
+                line: n + 1,
+                column: pos + 1,
+                string: None,
+            };
+            let len = if chars[pos] == '"' {
+                let (len, string) = read_string(&chars[pos..]).map_err(|(offset, message)| {
+                    let column = token.column + offset;
+                    Token {
+                        column,
+                        ..token.clone()
+                    }
+                    .error(message)
+                })?;
+                token.string = Some(string);
+                len
+            } else {
+                chars[pos..]
+                    .iter()
+                    .take_while(|c| !c.is_whitespace())
+                    .count()
+            };
+            token.text = chars[pos..pos + len].iter().collect();
+            tokens.push_back(token);
+            pos += len;
+        }
+    }
+    Ok(tokens)
+}
+
+// A string starting at its opening quote: its length in the source, quotes
+// included, and its contents. Errors give their offset from the quote.
+fn read_string(chars: &[char]) -> Result<(usize, String), (usize, String)> {
+    let mut string = String::new();
+    let mut pos = 1;
+    loop {
+        match chars.get(pos) {
+            None => return Err((0, "unterminated string".to_string())),
+            Some('"') => return Ok((pos + 1, string)),
+            Some('\\') => {
+                let escaped = match chars.get(pos + 1) {
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('v') => '\x0B',
+                    Some('0') => '\0',
+                    Some(&c @ ('\\' | '"' | '\'')) => c,
+                    Some(c) => return Err((pos, format!("unknown escape '\\{}'", c))),
+                    None => return Err((0, "unterminated string".to_string())),
+                };
+                string.push(escaped);
+                pos += 2;
+            }
+            Some(&c) => {
+                string.push(c);
+                pos += 1;
+            }
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn register_number(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    match u8::from_str_radix(digit, 16) {
+        Ok(n) if digit.len() == 1 => Some(n),
+        _ => None,
+    }
+}
+
+// How an address that isn't known yet gets patched in
+#[derive(Debug, Clone, Copy)]
+enum Patch {
+    // The low 12 bits of the instruction at the address
+    Short,
+    // Both bytes at the address
+    Long,
+    // `:unpack`: v0 := nibble << 4 | high nibble, v1 := low byte. With no
+    // nibble, `:unpack long`: v0 := high byte, v1 := low byte.
+    Unpack(Option<u8>),
+}
+
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+// The bodies of a `:stringmode` and, for each character of their alphabets,
+// which body it expands and its position in that alphabet
+#[derive(Default)]
+struct StringMode {
+    bodies: Vec<Vec<Token>>,
+    chars: HashMap<char, (usize, usize)>,
+}
+
+struct Compiler {
+    tokens: VecDeque<Token>,
+    memory: Vec<u8>,
+    here: usize,
+    end: usize,
+    // Whether 0x200 still holds the placeholder jump to main
+    jump_to_main: bool,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    string_modes: HashMap<String, StringMode>,
+    forward: Vec<(usize, Patch, Token)>,
+    // Loop start and the `while` jumps that leave it
+    loops: Vec<(usize, Vec<usize>, Token)>,
+    // Jumps of `begin`/`else` blocks waiting for their end
+    branches: Vec<(usize, Token)>,
+    expansions: usize,
+    last: Option<Token>,
+}
+
+// Reads a ROM file, compiling it first if it's Octo source (`.8o`)
+pub fn read_rom(filename: &str) -> Result<Vec<u8>, String> {
+    if Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext == "8o")
+    {
+        let source = std::fs::read_to_string(filename).map_err(|err| err.to_string())?;
+        compile(&source).map_err(|mut err| {
+            err.file = Some(filename.to_string());
+            err.to_string()
+        })
+    } else {
+        std::fs::read(filename).map_err(|err| err.to_string())
+    }
+}
+
+pub fn compile(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut compiler = Compiler {
+        tokens: tokenize(source)?,
+        memory: vec![0; MEMORY_SIZE],
+        here: ORIGIN,
+        end: ORIGIN,
+        jump_to_main: true,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        string_modes: HashMap::new(),
+        forward: Vec::new(),
+        loops: Vec::new(),
+        branches: Vec::new(),
+        expansions: 0,
+        last: None,
+    };
+    // Reserve the jump to main
+    compiler.emit(0x0000)?;
+    while !compiler.tokens.is_empty() {
+        compiler.statement()?;
+    }
+    compiler.finish()
+}
+
+impl Compiler {
+    fn next(&mut self) -> Result<Token, AsmError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.last = Some(token.clone());
+                Ok(token)
+            }
+            None => Err(self.end_error("unexpected end of program")),
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, AsmError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(token.error(format!("expected '{}', got '{}'", text, token.text)));
+        }
+        Ok(token)
+    }
+
+    fn end_error(&self, message: &str) -> AsmError {
+        match &self.last {
+            Some(token) => token.error(message),
+            None => AsmError {
+                file: None,
+                line: 1,
+                column: 1,
+                message: message.to_string(),
+            },
+        }
+    }
+
+    fn emit_byte(&mut self, token: Option<&Token>, byte: u8) -> Result<(), AsmError> {
+        if self.here >= MEMORY_SIZE {
+            let message = "program doesn't fit in memory";
+            return Err(token.map_or_else(|| self.end_error(message), |t| t.error(message)));
+        }
+        self.memory[self.here] = byte;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+
+    fn emit(&mut self, word: u16) -> Result<(), AsmError> {
+        let token = self.last.clone();
+        self.emit_byte(token.as_ref(), (word >> 8) as u8)?;
+        self.emit_byte(token.as_ref(), word as u8)
+    }
+
+    fn register(&mut self) -> Result<u8, AsmError> {
+        let token = self.next()?;
+        self.register_of(&token)
+            .ok_or_else(|| token.error(format!("expected a register, got '{}'", token.text)))
+    }
+
+    fn register_of(&self, token: &Token) -> Option<u8> {
+        register_number(&token.text).or_else(|| self.aliases.get(&token.text).copied())
+    }
+
+    fn peek_is_register(&self) -> bool {
+        self.tokens
+            .front()
+            .is_some_and(|token| self.register_of(token).is_some())
+    }
+
+    // A number, constant or already defined label
+    fn known_value(&self, token: &Token) -> Option<f64> {
+        if let Some(n) = parse_number(&token.text) {
+            return Some(n as f64);
+        }
+        if let Some(&value) = self.constants.get(&token.text) {
+            return Some(value);
+        }
+        self.labels.get(&token.text).map(|&addr| addr as f64)
+    }
+
+    fn value(&mut self, min: i64, max: i64) -> Result<i64, AsmError> {
+        let token = self.next()?;
+        let value = if token.text == "{" {
+            self.calc_block()?
+        } else {
+            self.known_value(&token)
+                .ok_or_else(|| token.error(format!("undefined name '{}'", token.text)))?
+        };
+        let value = value.floor() as i64;
+        if !(min..=max).contains(&value) {
+            return Err(token.error(format!("value {} out of range {}..={}", value, min, max)));
+        }
+        Ok(value)
+    }
+
+    fn byte_value(&mut self) -> Result<u8, AsmError> {
+        Ok(self.value(-128, 0xFF)? as u8)
+    }
+
+    fn nibble_value(&mut self) -> Result<u8, AsmError> {
+        Ok(self.value(0, 0xF)? as u8)
+    }
+
+    // An address operand, which may be a label defined further down
+    fn address(&mut self, patch: Patch) -> Result<u16, AsmError> {
+        let max = match patch {
+            Patch::Short | Patch::Unpack(Some(_)) => 0xFFF,
+            Patch::Long | Patch::Unpack(None) => 0xFFFF,
+        };
+        let token = self.tokens.front().cloned();
+        let Some(token) = token else {
+            return Err(self.end_error("expected an address"));
+        };
+        let forward = token.text != "{"
+            && self.known_value(&token).is_none()
+            && self.register_of(&token).is_none();
+        if forward {
+            self.next()?;
+            self.forward.push((self.here, patch, token));
+            return Ok(0);
+        }
+        Ok(self.value(0, max)? as u16)
+    }
+
+    fn define_label(&mut self, token: &Token, addr: usize) -> Result<(), AsmError> {
+        if self.labels.contains_key(&token.text) {
+            return Err(token.error(format!("the name '{}' is already defined", token.text)));
+        }
+        if register_number(&token.text).is_some() || parse_number(&token.text).is_some() {
+            return Err(token.error(format!("'{}' can't be used as a name", token.text)));
+        }
+        self.labels.insert(token.text.clone(), addr as u16);
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next()?;
+                // A program that starts with main doesn't need the jump to it
+                if name.text == "main" && self.here == ORIGIN + 2 && self.jump_to_main {
+                    self.here = ORIGIN;
+                    self.end = ORIGIN;
+                    self.jump_to_main = false;
+                }
+                self.define_label(&name, self.here)?;
+            }
+            ":next" => {
+                let name = self.next()?;
+                self.define_label(&name, self.here + 1)?;
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value(i64::MIN, i64::MAX)?;
+                self.constants.insert(name.text, value as f64);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc_block()?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = if self.peek() == Some("{") {
+                    self.next()?;
+                    let value = self.calc_block()?;
+                    if !(0.0..16.0).contains(&value) {
+                        return Err(name.error("alias must name a register v0 to vf"));
+                    }
+                    value as u8
+                } else {
+                    self.register()?
+                };
+                self.aliases.insert(name.text, register);
+            }
+            ":byte" => {
+                let value = self.byte_value()?;
+                self.emit_byte(Some(&token), value)?;
+            }
+            ":pointer" => {
+                let addr = self.address(Patch::Long)?;
+                self.emit(addr)?;
+            }
+            ":org" => {
+                let at = self.tokens.front().cloned();
+                let addr = self.value(0, MEMORY_SIZE as i64 - 1)? as usize;
+                // Only what's from 0x200 on ends up in the ROM
+                if addr < ORIGIN {
+                    let at = at.unwrap();
+                    return Err(at.error(format!(":org {} is below 0x200", at.text)));
+                }
+                self.here = addr;
+            }
+            ":call" => {
+                let addr = self.address(Patch::Short)?;
+                self.emit(0x2000 | addr)?;
+            }
+            ":unpack" => {
+                let nibble = if self.peek() == Some("long") {
+                    self.next()?;
+                    None
+                } else {
+                    Some(self.nibble_value()?)
+                };
+                let addr = self.address(Patch::Unpack(nibble))?;
+                self.emit(0x6000 | (nibble.unwrap_or(0) as u16) << 4 | addr >> 8)?;
+                self.emit(0x6100 | (addr & 0xFF))?;
+            }
+            ":macro" => self.define_macro()?,
+            ":stringmode" => self.define_string_mode()?,
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ":assert" => {
+                let mut message = None;
+                if self.peek() != Some("{") {
+                    let text = self.next()?;
+                    message = Some(text.string.unwrap_or(text.text));
+                }
+                self.expect("{")?;
+                if self.calc_block()? == 0.0 {
+                    return Err(token.error(match message {
+                        Some(message) => format!("assertion failed: {}", message),
+                        None => "assertion failed".to_string(),
+                    }));
+                }
+            }
+            "return" | ";" => self.emit(0x00EE)?,
+            "clear" => self.emit(0x00E0)?,
+            "exit" => self.emit(0x00FD)?,
+            "lores" => self.emit(0x00FE)?,
+            "hires" => self.emit(0x00FF)?,
+            "scroll-down" => {
+                let n = self.nibble_value()?;
+                self.emit(0x00C0 | n as u16)?;
+            }
+            "scroll-up" => {
+                let n = self.nibble_value()?;
+                self.emit(0x00D0 | n as u16)?;
+            }
+            "scroll-right" => self.emit(0x00FB)?,
+            "scroll-left" => self.emit(0x00FC)?,
+            "audio" => self.emit(0xF002)?,
+            "plane" => {
+                let n = self.nibble_value()?;
+                self.emit(0xF001 | (n as u16) << 8)?;
+            }
+            "bcd" => self.emit_fx(0x33)?,
+            "saveflags" => self.emit_fx(0x75)?,
+            "loadflags" => self.emit_fx(0x85)?,
+            "save" | "load" => {
+                let x = self.register()? as u16;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()? as u16;
+                    let n = if token.text == "save" { 2 } else { 3 };
+                    self.emit(0x5000 | x << 8 | y << 4 | n)?;
+                } else {
+                    let low = if token.text == "save" { 0x55 } else { 0x65 };
+                    self.emit(0xF000 | x << 8 | low)?;
+                }
+            }
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.nibble_value()? as u16;
+                self.emit(0xD000 | x << 8 | y << 4 | n)?;
+            }
+            "jump" => {
+                let addr = self.address(Patch::Short)?;
+                self.emit(0x1000 | addr)?;
+            }
+            "jump0" => {
+                let addr = self.address(Patch::Short)?;
+                self.emit(0xB000 | addr)?;
+            }
+            "native" => {
+                let addr = self.address(Patch::Short)?;
+                self.emit(addr)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let low = match token.text.as_str() {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.emit_fx(low)?;
+            }
+            "i" => self.index_statement()?,
+            "if" => self.if_statement()?,
+            "else" => {
+                let (jump, _) = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| token.error("'else' without 'if ... begin'"))?;
+                let skip = self.here;
+                self.emit(0x1000)?;
+                self.patch_jump(jump, self.here, &token)?;
+                self.branches.push((skip, token));
+            }
+            "end" => {
+                let (jump, _) = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| token.error("'end' without 'if ... begin'"))?;
+                self.patch_jump(jump, self.here, &token)?;
+            }
+            "loop" => self.loops.push((self.here, Vec::new(), token)),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err(token.error("'while' outside of a loop"));
+                }
+                self.conditional(true)?;
+                let jump = self.here;
+                self.emit(0x1000)?;
+                self.loops.last_mut().unwrap().1.push(jump);
+            }
+            "again" => {
+                let (start, exits, _) = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| token.error("'again' without 'loop'"))?;
+                if start > 0xFFF {
+                    return Err(token.error("loop starts beyond 0xFFF"));
+                }
+                self.emit(0x1000 | start as u16)?;
+                for jump in exits {
+                    self.patch_jump(jump, self.here, &token)?;
+                }
+            }
+            _ if self.register_of(&token).is_some() => {
+                let x = self.register_of(&token).unwrap();
+                self.register_statement(x)?;
+            }
+            _ if self.macros.contains_key(&token.text) => self.expand_macro(&token)?,
+            _ if self.string_modes.contains_key(&token.text) => self.expand_string_mode(&token)?,
+            _ if parse_number(&token.text).is_some()
+                || self.constants.contains_key(&token.text) =>
+            {
+                let value = self.known_value(&token).unwrap().floor() as i64;
+                if !(-128..=0xFF).contains(&value) {
+                    return Err(token.error(format!("value {} doesn't fit in a byte", value)));
+                }
+                self.emit_byte(Some(&token), value as u8)?;
+            }
+            _ if token.text.starts_with(':') || token.text == "{" || token.text == "}" => {
+                return Err(token.error(format!("unexpected '{}'", token.text)));
+            }
+            // Anything else names a subroutine, possibly one defined later
+            _ => {
+                self.tokens.push_front(token);
+                let addr = self.address(Patch::Short)?;
+                self.emit(0x2000 | addr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_fx(&mut self, low: u16) -> Result<(), AsmError> {
+        let x = self.register()? as u16;
+        self.emit(0xF000 | x << 8 | low)
+    }
+
+    fn index_statement(&mut self) -> Result<(), AsmError> {
+        let op = self.next()?;
+        match op.text.as_str() {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next()?;
+                    self.emit_fx(0x29)
+                }
+                Some("bighex") => {
+                    self.next()?;
+                    self.emit_fx(0x30)
+                }
+                Some("long") => {
+                    self.next()?;
+                    self.emit(0xF000)?;
+                    let addr = self.address(Patch::Long)?;
+                    self.emit(addr)
+                }
+                _ => {
+                    let addr = self.address(Patch::Short)?;
+                    self.emit(0xA000 | addr)
+                }
+            },
+            "+=" => self.emit_fx(0x1E),
+            _ => Err(op.error(format!("unknown operator 'i {}'", op.text))),
+        }
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), AsmError> {
+        let x16 = (x as u16) << 8;
+        let op = self.next()?;
+        let with_register = |low: u16, compiler: &mut Compiler| -> Result<(), AsmError> {
+            let y = compiler.register()? as u16;
+            compiler.emit(0x8000 | x16 | y << 4 | low)
+        };
+        match op.text.as_str() {
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    let kk = self.byte_value()? as u16;
+                    self.emit(0xC000 | x16 | kk)
+                }
+                Some("key") => {
+                    self.next()?;
+                    self.emit(0xF00A | x16)
+                }
+                Some("delay") => {
+                    self.next()?;
+                    self.emit(0xF007 | x16)
+                }
+                _ if self.peek_is_register() => with_register(0x0, self),
+                _ => {
+                    let kk = self.byte_value()? as u16;
+                    self.emit(0x6000 | x16 | kk)
+                }
+            },
+            "+=" if self.peek_is_register() => with_register(0x4, self),
+            "+=" => {
+                let kk = self.byte_value()? as u16;
+                self.emit(0x7000 | x16 | kk)
+            }
+            "-=" if self.peek_is_register() => with_register(0x5, self),
+            "-=" => {
+                let kk = self.byte_value()?.wrapping_neg() as u16;
+                self.emit(0x7000 | x16 | kk)
+            }
+            "|=" => with_register(0x1, self),
+            "&=" => with_register(0x2, self),
+            "^=" => with_register(0x3, self),
+            "=-" => with_register(0x7, self),
+            ">>=" => with_register(0x6, self),
+            "<<=" => with_register(0xE, self),
+            _ => Err(op.error(format!("unknown operator '{}'", op.text))),
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<(), AsmError> {
+        // Find out which form this is before emitting anything, the skip
+        // instruction depends on it
+        let form = self
+            .tokens
+            .iter()
+            .find(|token| token.text == "then" || token.text == "begin")
+            .map(|token| token.text.clone());
+        match form.as_deref() {
+            Some("then") => {
+                self.conditional(false)?;
+                self.expect("then")?;
+            }
+            Some("begin") => {
+                self.conditional(true)?;
+                let token = self.expect("begin")?;
+                let jump = self.here;
+                self.emit(0x1000)?;
+                self.branches.push((jump, token));
+            }
+            _ => {
+                let token = self.last.clone().unwrap();
+                return Err(token.error("'if' needs 'then' or 'begin'"));
+            }
+        }
+        Ok(())
+    }
+
+    // Emits the instruction(s) that skip the next one when the condition is
+    // false, or when it's true if `negated`
+    fn conditional(&mut self, negated: bool) -> Result<(), AsmError> {
+        let x = self.register()? as u16;
+        let op = self.next()?;
+        let mut comparison = op.text.clone();
+        if negated {
+            comparison = match comparison.as_str() {
+                "==" => "!=",
+                "!=" => "==",
+                "key" => "-key",
+                "-key" => "key",
+                "<" => ">=",
+                ">" => "<=",
+                ">=" => "<",
+                "<=" => ">",
+                other => return Err(op.error(format!("unknown comparison '{}'", other))),
+            }
+            .to_string();
+        }
+        match comparison.as_str() {
+            "==" | "!=" => {
+                let equal = comparison == "==";
+                if self.peek_is_register() {
+                    let y = self.register()? as u16;
+                    let base = if equal { 0x9000 } else { 0x5000 };
+                    self.emit(base | x << 8 | y << 4)
+                } else {
+                    let kk = self.byte_value()? as u16;
+                    let base = if equal { 0x4000 } else { 0x3000 };
+                    self.emit(base | x << 8 | kk)
+                }
+            }
+            "key" => self.emit(0xE0A1 | x << 8),
+            "-key" => self.emit(0xE09E | x << 8),
+            // VF := operand, then subtract so the borrow flag answers the question
+            ">" | "<" | ">=" | "<=" => {
+                if self.peek_is_register() {
+                    let y = self.register()? as u16;
+                    self.emit(0x8F00 | y << 4)?;
+                } else {
+                    let kk = self.byte_value()? as u16;
+                    self.emit(0x6F00 | kk)?;
+                }
+                let (subtract, skip) = match comparison.as_str() {
+                    ">" => (0x5, 0x4F00),
+                    "<" => (0x7, 0x4F00),
+                    ">=" => (0x7, 0x3F00),
+                    _ => (0x5, 0x3F00),
+                };
+                self.emit(0x8F00 | x << 4 | subtract)?;
+                self.emit(skip)
+            }
+            other => Err(op.error(format!("unknown comparison '{}'", other))),
+        }
+    }
+
+    fn define_macro(&mut self) -> Result<(), AsmError> {
+        let name = self.next()?;
+        let mut args = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "{" {
+                break;
+            }
+            args.push(token.text);
+        }
+        let body = self.braced_tokens()?;
+        self.macros.insert(name.text, Macro { args, body });
+        Ok(())
+    }
+
+    // Tokens up to the matching closing brace, the opening one already read
+    fn braced_tokens(&mut self) -> Result<Vec<Token>, AsmError> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    fn expand_macro(&mut self, name: &Token) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(name.error("too many macro expansions, is a macro recursive?"));
+        }
+        let arg_count = self.macros[&name.text].args.len();
+        let mut values = HashMap::new();
+        for n in 0..arg_count {
+            let value = self.next()?;
+            values.insert(self.macros[&name.text].args[n].clone(), value.text);
+        }
+        let expansion: Vec<Token> = self.macros[&name.text]
+            .body
+            .iter()
+            .map(|token| Token {
+                text: values.get(&token.text).unwrap_or(&token.text).clone(),
+                ..token.clone()
+            })
+            .collect();
+        for token in expansion.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    // `:stringmode name "alphabet" { body }`. Defining the same name again
+    // adds another alphabet, and a character in both expands the later body.
+    fn define_string_mode(&mut self) -> Result<(), AsmError> {
+        let name = self.next()?;
+        let alphabet = self.string()?;
+        self.expect("{")?;
+        let body = self.braced_tokens()?;
+        let mode = self.string_modes.entry(name.text).or_default();
+        for (value, c) in alphabet.chars().enumerate() {
+            mode.chars.insert(c, (mode.bodies.len(), value));
+        }
+        mode.bodies.push(body);
+        Ok(())
+    }
+
+    // `name "text"` expands the body for each character in turn, with CHAR
+    // its character code, INDEX its position in the text and VALUE its
+    // position in the alphabet
+    fn expand_string_mode(&mut self, name: &Token) -> Result<(), AsmError> {
+        let text = self.string()?;
+        let mode = &self.string_modes[&name.text];
+        let mut expansion = Vec::new();
+        for (index, c) in text.chars().enumerate() {
+            let Some(&(body, value)) = mode.chars.get(&c) else {
+                return Err(name.error(format!(
+                    "string mode '{}' has no character {:?}",
+                    name.text, c
+                )));
+            };
+            let number = |text: &str| match text {
+                "CHAR" => Some(c as u32 as usize),
+                "INDEX" => Some(index),
+                "VALUE" => Some(value),
+                _ => None,
+            };
+            expansion.extend(mode.bodies[body].iter().map(|token| Token {
+                text: number(&token.text).map_or_else(|| token.text.clone(), |n| n.to_string()),
+                ..token.clone()
+            }));
+        }
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(name.error("too many macro expansions, is a macro recursive?"));
+        }
+        for token in expansion.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String, AsmError> {
+        let token = self.next()?;
+        match token.string {
+            Some(string) => Ok(string),
+            None => Err(token.error(format!("expected a string, got '{}'", token.text))),
+        }
+    }
+
+    // `{ expr }`, the opening brace already read
+    fn calc_block(&mut self) -> Result<f64, AsmError> {
+        let tokens = self.braced_tokens()?;
+        let mut pos = 0;
+        let value = self.calc_expr(&tokens, &mut pos)?;
+        if let Some(token) = tokens.get(pos) {
+            return Err(token.error(format!("unexpected '{}' in expression", token.text)));
+        }
+        Ok(value)
+    }
+
+    // Octo evaluates right to left with no precedence: 1 - 2 - 3 is 1 - (2 - 3)
+    fn calc_expr(&self, tokens: &[Token], pos: &mut usize) -> Result<f64, AsmError> {
+        let left = self.calc_term(tokens, pos)?;
+        let Some(op) = tokens.get(*pos) else {
+            return Ok(left);
+        };
+        if op.text == ")" {
+            return Ok(left);
+        }
+        *pos += 1;
+        let right = self.calc_expr(tokens, pos)?;
+        let (a, b) = (left, right);
+        let bool_value = |b: bool| if b { 1.0 } else { 0.0 };
+        let value = match op.text.as_str() {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            "/" => a / b,
+            "%" => a % b,
+            "&" => ((a as i64) & (b as i64)) as f64,
+            "|" => ((a as i64) | (b as i64)) as f64,
+            "^" => ((a as i64) ^ (b as i64)) as f64,
+            "<<" => ((a as i64) << (b as i64)) as f64,
+            ">>" => ((a as i64) >> (b as i64)) as f64,
+            "pow" => a.powf(b),
+            "min" => a.min(b),
+            "max" => a.max(b),
+            "<" => bool_value(a < b),
+            "<=" => bool_value(a <= b),
+            ">" => bool_value(a > b),
+            ">=" => bool_value(a >= b),
+            "==" => bool_value(a == b),
+            "!=" => bool_value(a != b),
+            _ => return Err(op.error(format!("unknown operator '{}'", op.text))),
+        };
+        Ok(value)
+    }
+
+    fn calc_term(&self, tokens: &[Token], pos: &mut usize) -> Result<f64, AsmError> {
+        let Some(token) = tokens.get(*pos) else {
+            return Err(self.end_error("expression is missing a value"));
+        };
+        *pos += 1;
+        let unary: Option<fn(f64) -> f64> = match token.text.as_str() {
+            "-" => Some(|a| -a),
+            "~" => Some(|a| !(a as i64) as f64),
+            "!" => Some(|a| if a == 0.0 { 1.0 } else { 0.0 }),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(function) = unary {
+            return Ok(function(self.calc_term(tokens, pos)?));
+        }
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc_expr(tokens, pos)?;
+                match tokens.get(*pos) {
+                    Some(close) if close.text == ")" => *pos += 1,
+                    _ => return Err(token.error("unclosed '('")),
+                }
+                Ok(value)
+            }
+            // The byte already compiled at an address
+            "@" => {
+                let addr = self.calc_term(tokens, pos)? as usize;
+                Ok(self.memory.get(addr).copied().unwrap_or(0) as f64)
+            }
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => {
+                if let Some(register) = self.register_of(token) {
+                    return Ok(register as f64);
+                }
+                self.known_value(token)
+                    .ok_or_else(|| token.error(format!("undefined name '{}'", token.text)))
+            }
+        }
+    }
+
+    fn patch_jump(&mut self, at: usize, target: usize, token: &Token) -> Result<(), AsmError> {
+        if target > 0xFFF {
+            return Err(token.error("jump target beyond 0xFFF"));
+        }
+        self.memory[at] = 0x10 | (target >> 8) as u8;
+        self.memory[at + 1] = target as u8;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, AsmError> {
+        if let Some((_, _, token)) = self.loops.last() {
+            return Err(token.error("'loop' without 'again'"));
+        }
+        if let Some((_, token)) = self.branches.last() {
+            return Err(token.error("'begin' without 'end'"));
+        }
+
+        for (at, patch, token) in std::mem::take(&mut self.forward) {
+            let addr = *self
+                .labels
+                .get(&token.text)
+                .ok_or_else(|| token.error(format!("undefined name '{}'", token.text)))?
+                as usize;
+            match patch {
+                Patch::Short => {
+                    if addr > 0xFFF {
+                        return Err(token.error("address beyond 0xFFF, use a long form"));
+                    }
+                    self.memory[at] |= (addr >> 8) as u8;
+                    self.memory[at + 1] = addr as u8;
+                }
+                Patch::Long => {
+                    self.memory[at] = (addr >> 8) as u8;
+                    self.memory[at + 1] = addr as u8;
+                }
+                Patch::Unpack(nibble) => {
+                    if nibble.is_some() && addr > 0xFFF {
+                        return Err(token.error("address beyond 0xFFF, use ':unpack long'"));
+                    }
+                    self.memory[at + 1] = nibble.unwrap_or(0) << 4 | (addr >> 8) as u8;
+                    self.memory[at + 3] = addr as u8;
+                }
+            }
+        }
+
+        let main = *self
+            .labels
+            .get("main")
+            .ok_or_else(|| self.end_error("the program doesn't define a 'main' label"))?;
+        if self.jump_to_main {
+            if main > 0xFFF {
+                return Err(self.end_error("'main' is beyond 0xFFF"));
+            }
+            self.memory[ORIGIN] = 0x10 | (main >> 8) as u8;
+            self.memory[ORIGIN + 1] = main as u8;
+        }
+        Ok(self.memory[ORIGIN..self.end].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_at(source: &str) -> (usize, usize, String) {
+        let err = compile(source).unwrap_err();
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn test_compile_loop_with_forward_label() {
+        let source = "
+:const SPEED 3
+: main
+  i := sprite
+  v0 := 10
+  loop
+    sprite v0 v1 5
+    v0 += SPEED
+    if v0 == 40 then v0 := 0
+  again
+: sprite
+  0x3C 0x42
+";
+        assert_eq!(
+            compile(source),
+            Ok(vec![
+                0xA2, 0x0E, 0x60, 0x0A, 0xD0, 0x15, 0x70, 0x03, 0x40, 0x28, 0x60, 0x00, 0x12, 0x04,
+                0x3C, 0x42,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_jump_to_main_when_it_isnt_first() {
+        let source = ": helper return\n: main helper";
+        assert_eq!(
+            compile(source),
+            Ok(vec![0x12, 0x04, 0x00, 0xEE, 0x22, 0x02])
+        );
+    }
+
+    #[test]
+    fn test_if_begin_else_end_and_while() {
+        let source = "
+: main
+  if v1 != v2 begin
+    v3 := key
+  else
+    v3 -= 1
+  end
+  loop
+    while v4 key
+    v4 =- v5
+  again
+";
+        assert_eq!(
+            compile(source),
+            Ok(vec![
+                // 200: SNE V1, V2 (skip the jump when they differ), JP 208
+                0x91, 0x20, 0x12, 0x08, // 204: v3 := key, JP 20A
+                0xF3, 0x0A, 0x12, 0x0A, // 208: v3 += 0xFF
+                0x73, 0xFF, // 20A: skip the exit when the key is down, JP 212
+                0xE4, 0x9E, 0x12, 0x12, // 20E: v4 =- v5, JP 20A
+                0x84, 0x57, 0x12, 0x0A,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_comparison_pseudo_ops() {
+        assert_eq!(
+            compile(": main if v1 > 5 then clear"),
+            Ok(vec![0x6F, 0x05, 0x8F, 0x15, 0x4F, 0x00, 0x00, 0xE0])
+        );
+        assert_eq!(
+            compile(": main if v1 <= v2 then clear"),
+            Ok(vec![0x8F, 0x20, 0x8F, 0x15, 0x3F, 0x00, 0x00, 0xE0])
+        );
+    }
+
+    #[test]
+    fn test_macros_calc_and_alias() {
+        let source = "
+:alias x v6
+:calc HALF { 64 / 2 }
+:calc RIGHT { 10 - 4 - 1 }
+:macro move REG AMOUNT { REG += AMOUNT }
+: main
+  x := HALF
+  move x RIGHT
+  :byte { HALF + 1 }
+";
+        // 10 - (4 - 1) = 7, right to left
+        assert_eq!(compile(source), Ok(vec![0x66, 0x20, 0x76, 0x07, 0x21]));
+    }
+
+    #[test]
+    fn test_long_unpack_next_and_xochip() {
+        let source = "
+: main
+  i := long data
+  :unpack 0xA data
+  plane 3
+  save v1 - v4
+  :next target
+  v2 := 0
+:org 0x300
+: data
+  :pointer main
+";
+        assert_eq!(
+            compile(source).unwrap()[..16],
+            [
+                0xF0, 0x00, 0x03, 0x00, 0x60, 0xA3, 0x61, 0x00, 0xF3, 0x01, 0x51, 0x42, 0x62, 0x00,
+                0x00, 0x00,
+            ]
+        );
+        let rom = compile(source).unwrap();
+        assert_eq!(rom[0x100..], [0x02, 0x00]);
+    }
+
+    #[test]
+    fn test_errors_give_line_and_column() {
+        assert_eq!(
+            error_at(": main\n  jump nowhere"),
+            (2, 8, "undefined name 'nowhere'".to_string())
+        );
+        assert_eq!(
+            error_at(": main\n  v0 := 300"),
+            (2, 9, "value 300 out of range -128..=255".to_string())
+        );
+        assert_eq!(
+            error_at(": start clear"),
+            (
+                1,
+                9,
+                "the program doesn't define a 'main' label".to_string()
+            )
+        );
+        assert_eq!(
+            error_at(": main loop clear"),
+            (1, 8, "'loop' without 'again'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unpack_nibble_needs_a_12_bit_address() {
+        assert_eq!(
+            error_at(": main\n  :unpack 0xA 0x1234"),
+            (2, 15, "value 4660 out of range 0..=4095".to_string())
+        );
+        let source = ": main\n  :unpack 0xA data\n:org 0x1000\n: data";
+        assert_eq!(
+            error_at(source),
+            (
+                2,
+                15,
+                "address beyond 0xFFF, use ':unpack long'".to_string()
+            )
+        );
+        let source = ": main\n  :unpack long data\n:org 0x1234\n: data";
+        assert_eq!(compile(source).unwrap()[..4], [0x60, 0x12, 0x61, 0x34]);
+    }
+
+    #[test]
+    fn test_org_below_the_program_is_an_error() {
+        assert_eq!(
+            error_at(":org 0x100\n: main\n  clear"),
+            (1, 6, ":org 0x100 is below 0x200".to_string())
+        );
+    }
+
+    #[test]
+    fn test_string_modes() {
+        let source = "
+:stringmode text \"ABC\" { :byte { VALUE + 1 } }
+:stringmode text \" \" { :byte 0 }
+:stringmode ascii \"xyz\" { :byte CHAR :byte INDEX }
+: main
+  text \"CAB A\"
+  ascii \"zx\"
+";
+        assert_eq!(
+            compile(source),
+            Ok(vec![0x03, 0x01, 0x02, 0x00, 0x01, 0x7A, 0x00, 0x78, 0x01])
+        );
+        assert_eq!(
+            error_at(":stringmode digits \"0123\" { :byte VALUE }\n: main digits \"12a\""),
+            (
+                2,
+                8,
+                "string mode 'digits' has no character 'a'".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_quoted_strings_are_single_tokens() {
+        // Neither the brace nor the # inside the quotes count
+        assert_eq!(
+            compile(":assert \"a } # b\" { 1 }\n: main clear"),
+            Ok(vec![0x00, 0xE0])
+        );
+        assert_eq!(
+            error_at(": main\n  :assert \"too \\\"big\\\"\" { 1 > 2 }"),
+            (2, 3, "assertion failed: too \"big\"".to_string())
+        );
+        assert_eq!(
+            error_at(": main\n  :assert \"oops { 1 }"),
+            (2, 11, "unterminated string".to_string())
+        );
+        assert_eq!(
+            error_at(": main\n  :assert \"a\\qb\" { 1 }"),
+            (2, 13, "unknown escape '\\q'".to_string())
+        );
+    }
+}
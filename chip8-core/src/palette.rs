@@ -0,0 +1,145 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::screen::DEFAULT_COLORS;
+
+// RGB colors indexed by pixel value: background, plane 1, plane 2 and both
+// planes. Plain CHIP-8 programs only ever use the first two.
+pub type Palette = [[u8; 3]; 4];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    #[default]
+    Default,
+    // A green phosphor CRT, like the terminals of the era
+    Green,
+    Amber,
+    // Dark pixels on a pale green-gray panel
+    Lcd,
+    // The Octo IDE's default colors, what most XO-CHIP games were made with
+    Octo,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 5] = [
+        Theme::Default,
+        Theme::Green,
+        Theme::Amber,
+        Theme::Lcd,
+        Theme::Octo,
+    ];
+
+    pub fn colors(self) -> Palette {
+        match self {
+            Theme::Default => DEFAULT_COLORS,
+            Theme::Green => [
+                [0x05, 0x14, 0x08],
+                [0x33, 0xFF, 0x66],
+                [0x1A, 0x80, 0x33],
+                [0x99, 0xFF, 0xB3],
+            ],
+            Theme::Amber => [
+                [0x1A, 0x0E, 0x00],
+                [0xFF, 0xB0, 0x00],
+                [0x99, 0x5C, 0x00],
+                [0xFF, 0xDD, 0x88],
+            ],
+            Theme::Lcd => [
+                [0x9B, 0xBC, 0x0F],
+                [0x0F, 0x38, 0x0F],
+                [0x8B, 0xAC, 0x0F],
+                [0x30, 0x62, 0x30],
+            ],
+            Theme::Octo => [
+                [0x99, 0x66, 0x00],
+                [0xFF, 0xCC, 0x00],
+                [0xFF, 0x66, 0x00],
+                [0x66, 0x22, 0x00],
+            ],
+        }
+    }
+
+    // The one after this in ALL, wrapping around, for cycling with a hotkey
+    pub fn next(self) -> Theme {
+        let index = Theme::ALL.iter().position(|&theme| theme == self).unwrap();
+        Theme::ALL[(index + 1) % Theme::ALL.len()]
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(Theme::Default),
+            "green" => Ok(Theme::Green),
+            "amber" => Ok(Theme::Amber),
+            "lcd" => Ok(Theme::Lcd),
+            "octo" => Ok(Theme::Octo),
+            _ => Err(format!(
+                "Unknown theme '{}', expected default, green, amber, lcd or octo",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Theme::Default => write!(f, "default"),
+            Theme::Green => write!(f, "green"),
+            Theme::Amber => write!(f, "amber"),
+            Theme::Lcd => write!(f, "lcd"),
+            Theme::Octo => write!(f, "octo"),
+        }
+    }
+}
+
+// Colors are written as in HTML, RRGGBB hex with an optional leading #
+pub fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let error = || format!("Invalid color '{}', expected #RRGGBB", s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(error());
+    }
+    let mut color = [0; 3];
+    for (channel, pair) in color.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).unwrap();
+        *channel = u8::from_str_radix(pair, 16).map_err(|_| error())?;
+    }
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_names() {
+        for theme in Theme::ALL {
+            assert_eq!(theme.to_string().parse(), Ok(theme));
+        }
+        assert_eq!("Amber".parse(), Ok(Theme::Amber));
+        assert!("sepia".parse::<Theme>().is_err());
+    }
+
+    #[test]
+    fn test_next_cycles_through_all_themes() {
+        let mut theme = Theme::default();
+        for expected in Theme::ALL.iter().skip(1) {
+            theme = theme.next();
+            assert_eq!(theme, *expected);
+        }
+        assert_eq!(theme.next(), Theme::Default);
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#FFCC00"), Ok([0xFF, 0xCC, 0x00]));
+        assert_eq!(parse_color("0f380f"), Ok([0x0F, 0x38, 0x0F]));
+        assert!(parse_color("#FFF").is_err());
+        assert!(parse_color("#GG0000").is_err());
+        assert!(parse_color("#ÿÿÿ").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::checksum::{adler32, crc32_update};
+use crate::screen::Screen;
+
+// A minimal PNG writer: 8-bit RGB, no interlacing, and the image data kept in
+// stored (uncompressed) deflate blocks. CHIP-8 frames are tiny, so skipping
+// compression keeps this dependency free at a cost of a few kilobytes.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+// `rgb` holds three bytes per pixel, row by row
+pub fn encode_rgb(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    assert_eq!(
+        rgb.len(),
+        width as usize * height as usize * 3,
+        "image data doesn't match its size"
+    );
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), default compression, filter and no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    // Every scanline starts with its filter type, 0 being none
+    let row_len = width as usize * 3;
+    let mut raw = Vec::with_capacity((row_len + 1) * height as usize);
+    for row in rgb.chunks(row_len.max(1)).take(height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+// What the screen shows in `colors`, indexed by pixel value, with every
+// CHIP-8 pixel drawn as a `scale` x `scale` block. A scale of 1 gives the
+// native 64x32 or 128x64.
+pub fn screenshot(screen: &Screen, colors: &[[u8; 3]; 4], scale: u32) -> Vec<u8> {
+    let scale = scale.max(1) as usize;
+    let (width, height) = (screen.width(), screen.height());
+    let native = screen.to_rgb(colors);
+    let mut rgb = Vec::with_capacity(native.len() * scale * scale);
+    for row in native.chunks(width * 3) {
+        let mut scaled_row = Vec::with_capacity(row.len() * scale);
+        for pixel in row.chunks(3) {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..scale {
+            rgb.extend_from_slice(&scaled_row);
+        }
+    }
+    encode_rgb((width * scale) as u32, (height * scale) as u32, &rgb)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    // The CRC covers the chunk type and data, not the length
+    let crc = crc32_update(crc32_update(0, kind), data);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32K window, no preset dictionary, fastest level
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        out.push(last as u8);
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::crc32;
+
+    // Splits a PNG into (type, data) chunks, checking every CRC on the way
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut chunks = Vec::new();
+        let mut pos = 8;
+        while pos < png.len() {
+            let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let body = &png[pos + 4..pos + 8 + len];
+            let crc = u32::from_be_bytes(png[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+            assert_eq!(crc32(body), crc);
+            chunks.push((
+                String::from_utf8(body[..4].to_vec()).unwrap(),
+                body[4..].to_vec(),
+            ));
+            pos += 12 + len;
+        }
+        chunks
+    }
+
+    // Undoes `zlib_stored`, which is all a stored-only stream needs
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut pos = 2;
+        loop {
+            let last = zlib[pos] & 1 != 0;
+            let len = u16::from_le_bytes([zlib[pos + 1], zlib[pos + 2]]) as usize;
+            out.extend_from_slice(&zlib[pos + 5..pos + 5 + len]);
+            pos += 5 + len;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(zlib[pos..], adler32(&out).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn test_encode_rgb_chunks_and_scanlines() {
+        let rgb = [255, 0, 0, 0, 255, 0, 0, 0, 255, 1, 2, 3];
+        let png = encode_rgb(2, 2, &rgb);
+        let chunks = chunks(&png);
+        let names: Vec<&str> = chunks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+        assert_eq!(
+            inflate_stored(&chunks[1].1),
+            [0, 255, 0, 0, 0, 255, 0, 0, 0, 0, 255, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_screenshot_native_and_scaled() {
+        let mut screen = Screen::new();
+        screen.set_screen(1, 0);
+        let colors = [[0, 0, 0], [9, 8, 7], [0, 0, 0], [0, 0, 0]];
+
+        let native = chunks(&screenshot(&screen, &colors, 1));
+        assert_eq!(native[0].1[..8], [0, 0, 0, 64, 0, 0, 0, 32]);
+        let raw = inflate_stored(&native[1].1);
+        assert_eq!(raw[..7], [0, 0, 0, 0, 9, 8, 7]);
+
+        let scaled = chunks(&screenshot(&screen, &colors, 3));
+        assert_eq!(scaled[0].1[..8], [0, 0, 0, 192, 0, 0, 0, 96]);
+        let raw = inflate_stored(&scaled[1].1);
+        let row_len = 1 + 192 * 3;
+        assert_eq!(raw.len(), row_len * 96);
+        // Rows 0-2 have pixels 3-5 lit, row 3 is the next CHIP-8 row
+        for row in 0..4 {
+            let lit = &raw[row * row_len + 1 + 3 * 3..row * row_len + 1 + 6 * 3];
+            let expected = if row < 3 { [9, 8, 7] } else { [0, 0, 0] };
+            assert!(lit.chunks(3).all(|pixel| pixel == expected));
+            assert_eq!(raw[row * row_len + 1 + 6 * 3], 0);
+        }
+    }
+
+    #[test]
+    fn test_large_images_span_several_blocks() {
+        let rgb = vec![0x7F; 200 * 150 * 3];
+        let png = encode_rgb(200, 150, &rgb);
+        let raw = inflate_stored(&chunks(&png)[1].1);
+        assert_eq!(raw.len(), 150 * 601);
+        assert!(raw
+            .chunks(601)
+            .all(|row| row[0] == 0 && row[1..] == rgb[..600]));
+    }
+}
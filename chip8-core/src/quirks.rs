@@ -0,0 +1,126 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Behaviors of ambiguous instructions that differ between CHIP-8
+// interpreters. Everything off is the behavior this interpreter always had.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    // 8xy6/8xyE shift Vy into Vx instead of shifting Vx in place
+    pub shift_uses_vy: bool,
+    // Fx55/Fx65 leave I pointing past the last register stored or loaded
+    pub load_store_increments_i: bool,
+    // Bnnn jumps to nnn + Vx (x being the high nibble of nnn) instead of nnn + V0
+    pub jump_uses_vx: bool,
+    // 8xy1/8xy2/8xy3 set VF to 0
+    pub logic_resets_vf: bool,
+    // Dxyn clips sprites at the screen edges instead of wrapping them around
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+    // Packs the toggles into a byte, one bit per quirk in declaration order,
+    // for the on-disk formats
+    pub fn to_bits(self) -> u8 {
+        [
+            self.shift_uses_vy,
+            self.load_store_increments_i,
+            self.jump_uses_vx,
+            self.logic_resets_vf,
+            self.clip_sprites,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (n, &on)| bits | ((on as u8) << n))
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Quirks {
+            shift_uses_vy: bits & 0x01 != 0,
+            load_store_increments_i: bits & 0x02 != 0,
+            jump_uses_vx: bits & 0x04 != 0,
+            logic_resets_vf: bits & 0x08 != 0,
+            clip_sprites: bits & 0x10 != 0,
+        }
+    }
+
+    pub fn presets() -> [(&'static str, Quirks); 4] {
+        [
+            ("vip", Quirks::vip()),
+            ("chip48", Quirks::chip48()),
+            ("schip", Quirks::schip()),
+            ("xochip", Quirks::xochip()),
+        ]
+    }
+
+    // The original COSMAC VIP interpreter
+    pub fn vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            clip_sprites: true,
+        }
+    }
+
+    // CHIP-48 on the HP-48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: true,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+        }
+    }
+
+    // SUPER-CHIP 1.1
+    pub fn schip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+        }
+    }
+
+    // XO-CHIP, as implemented by Octo
+    pub fn xochip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+        }
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.to_ascii_lowercase();
+        match Quirks::presets().iter().find(|(preset, _)| *preset == name) {
+            Some((_, quirks)) => Ok(*quirks),
+            None => {
+                let names: Vec<&str> = Quirks::presets().iter().map(|(name, _)| *name).collect();
+                Err(format!(
+                    "unknown quirks preset '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match Quirks::presets().iter().find(|(_, quirks)| quirks == self) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => write!(f, "custom"),
+        }
+    }
+}
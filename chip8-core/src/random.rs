@@ -0,0 +1,158 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::RngCore;
+
+// SplitMix64's increment, any seed works including 0
+const SPLITMIX_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+// State of a built-in generator, small enough to go into save states and
+// input movies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngState {
+    SplitMix(u64),
+}
+
+// Where Cxkk gets its random bytes from
+pub enum Random {
+    Builtin(RngState),
+    // Supplied through the library, not captured by snapshots
+    Custom(Box<dyn RngCore + Send>),
+}
+
+impl Random {
+    pub fn seeded(seed: u64) -> Random {
+        Random::Builtin(RngState::SplitMix(seed))
+    }
+
+    // Seeded from the OS, what a machine gets unless told otherwise
+    pub fn from_entropy() -> Random {
+        Random::seeded(entropy_seed())
+    }
+
+    pub fn custom(rng: impl RngCore + Send + 'static) -> Random {
+        Random::Custom(Box::new(rng))
+    }
+
+    pub fn state(&self) -> Option<RngState> {
+        match self {
+            Random::Builtin(state) => Some(*state),
+            Random::Custom(_) => None,
+        }
+    }
+
+    pub(crate) fn next_byte(&mut self) -> u8 {
+        match self {
+            Random::Builtin(RngState::SplitMix(state)) => {
+                *state = state.wrapping_add(SPLITMIX_GAMMA);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                (z ^ (z >> 31)) as u8
+            }
+            Random::Custom(rng) => rng.next_u32() as u8,
+        }
+    }
+}
+
+impl From<RngState> for Random {
+    fn from(state: RngState) -> Self {
+        Random::Builtin(state)
+    }
+}
+
+// Which generator the frontends build from a seed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RngKind {
+    #[default]
+    SplitMix,
+}
+
+impl RngKind {
+    pub fn with_seed(self, seed: u64) -> Random {
+        match self {
+            RngKind::SplitMix => Random::seeded(seed),
+        }
+    }
+}
+
+impl FromStr for RngKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "splitmix" | "default" => Ok(RngKind::SplitMix),
+            // An imitation of the COSMAC VIP's generator that never produced
+            // the VIP's sequence, removed rather than kept as a trap
+            "vip" | "vip-style" => Err(format!(
+                "The '{}' RNG is no longer supported, use splitmix",
+                s
+            )),
+            _ => Err(format!("Unknown RNG '{}', expected splitmix", s)),
+        }
+    }
+}
+
+impl fmt::Display for RngKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RngKind::SplitMix => write!(f, "splitmix"),
+        }
+    }
+}
+
+// For frontends that want to show the seed they picked, so the run can be
+// reproduced
+pub fn entropy_seed() -> u64 {
+    rand::random()
+}
+
+// Seeds are decimal or 0x-prefixed hex, like addresses elsewhere
+pub fn parse_seed(s: &str) -> Result<u64, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid seed '{}'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(random: &mut Random, count: usize) -> Vec<u8> {
+        (0..count).map(|_| random.next_byte()).collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let first = bytes(&mut Random::seeded(42), 64);
+        assert_eq!(bytes(&mut Random::seeded(42), 64), first);
+        assert_ne!(bytes(&mut Random::seeded(43), 64), first);
+    }
+
+    #[test]
+    fn test_state_resumes_sequence() {
+        let mut random = Random::seeded(7);
+        bytes(&mut random, 10);
+        let mut resumed = Random::from(random.state().unwrap());
+        assert_eq!(bytes(&mut resumed, 10), bytes(&mut random, 10));
+    }
+
+    #[test]
+    fn test_custom_rng_has_no_state() {
+        let mut random = Random::custom(rand::rngs::mock::StepRng::new(0x101, 1));
+        assert_eq!(random.state(), None);
+        assert_eq!(bytes(&mut random, 2), [0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_parse_seed() {
+        assert_eq!(parse_seed("1234"), Ok(1234));
+        assert_eq!(parse_seed("0xBEEF"), Ok(0xBEEF));
+        assert!(parse_seed("seed").is_err());
+        assert_eq!("SplitMix".parse(), Ok(RngKind::SplitMix));
+        assert!("vip-style".parse::<RngKind>().is_err());
+        assert!("lfsr".parse::<RngKind>().is_err());
+    }
+}
@@ -0,0 +1,544 @@
+// A deliberately simple CHIP-8, written from the specs as a second opinion
+// on `Chip8::execute`. It works directly on a `Snapshot`, decodes opcodes by
+// matching nibbles itself and spells every rule out rather than sharing
+// anything with the real interpreter. The property tests below run random
+// opcodes on random machines through both and compare the complete state.
+
+use crate::chip::AUDIO_PATTERN_SIZE;
+use crate::error::{CpuError, StepOutcome};
+use crate::keyboard::Chip8Key;
+use crate::mode::Mode;
+use crate::screen::{CHIP8_HEIGHT, CHIP8_WIDTH, SCHIP_HEIGHT, SCHIP_WIDTH};
+use crate::snapshot::Snapshot;
+
+const SMALL_FONT_HEIGHT: u16 = 5;
+const BIG_FONT_ADDRESS: u16 = 0x50;
+const BIG_FONT_HEIGHT: u16 = 10;
+const STACK_DEPTH: u8 = 16;
+const SCHIP_SCROLL: isize = 4;
+
+// Executes one opcode whose fetch already moved the program counter past it.
+// On a fault the state is left exactly as it was. `random` is the byte Cxkk
+// draws, before masking, the RNG state is left to the caller.
+pub fn exec(state: &mut Snapshot, opcode: u16, random: u8) -> Result<StepOutcome, CpuError> {
+    let mut next = state.clone();
+    let outcome = exec_in_place(&mut next, opcode, random)?;
+    *state = next;
+    Ok(outcome)
+}
+
+fn exec_in_place(s: &mut Snapshot, opcode: u16, random: u8) -> Result<StepOutcome, CpuError> {
+    let nibbles = (
+        (opcode >> 12) as u8,
+        (opcode >> 8 & 0xF) as u8,
+        (opcode >> 4 & 0xF) as u8,
+        (opcode & 0xF) as u8,
+    );
+    let (x, y) = (nibbles.1 as usize, nibbles.2 as usize);
+    let kk = opcode as u8;
+    let nnn = opcode & 0xFFF;
+    let xo_chip = s.mode == Mode::XoChip;
+    let unknown = CpuError::UnknownOpcode {
+        pc: s.program_counter.wrapping_sub(2),
+        opcode,
+    };
+
+    match nibbles {
+        (0x0, 0x0, 0xE, 0x0) => {
+            let keep = !s.planes;
+            for pixel in s.pixels.iter_mut() {
+                *pixel &= keep;
+            }
+        }
+        (0x0, 0x0, 0xE, 0xE) => {
+            if s.stack_pointer == 0 {
+                return Err(CpuError::StackUnderflow);
+            }
+            s.stack_pointer -= 1;
+            s.program_counter = s.stack[s.stack_pointer as usize];
+        }
+        (0x0, 0x0, 0xC, n) => scroll(s, 0, n as isize),
+        (0x0, 0x0, 0xD, n) if xo_chip => scroll(s, 0, -(n as isize)),
+        (0x0, 0x0, 0xF, 0xB) => scroll(s, SCHIP_SCROLL, 0),
+        (0x0, 0x0, 0xF, 0xC) => scroll(s, -SCHIP_SCROLL, 0),
+        (0x0, 0x0, 0xF, 0xD) => return Ok(StepOutcome::Exit),
+        (0x0, 0x0, 0xF, 0xE) | (0x0, 0x0, 0xF, 0xF) => {
+            s.hires = opcode == 0x00FF;
+            s.pixels.iter_mut().for_each(|pixel| *pixel = 0);
+        }
+        (0x1, ..) => s.program_counter = nnn,
+        (0x2, ..) => {
+            if s.stack_pointer == STACK_DEPTH {
+                return Err(CpuError::StackOverflow);
+            }
+            s.stack[s.stack_pointer as usize] = s.program_counter;
+            s.stack_pointer += 1;
+            s.program_counter = nnn;
+        }
+        (0x3, ..) => skip(s, s.v[x] == kk)?,
+        (0x4, ..) => skip(s, s.v[x] != kk)?,
+        (0x5, _, _, 0x0) => skip(s, s.v[x] == s.v[y])?,
+        // 5xy2/5xy3 walk from x to y, backwards if x > y
+        (0x5, _, _, 0x2) | (0x5, _, _, 0x3) if xo_chip => {
+            let registers: Vec<usize> = if x <= y {
+                (x..=y).collect()
+            } else {
+                (y..=x).rev().collect()
+            };
+            let start = s.i as usize;
+            check(s, start, registers.len())?;
+            for (offset, &reg) in registers.iter().enumerate() {
+                if nibbles.3 == 0x2 {
+                    s.memory[start + offset] = s.v[reg];
+                } else {
+                    s.v[reg] = s.memory[start + offset];
+                }
+            }
+        }
+        (0x6, ..) => s.v[x] = kk,
+        (0x7, ..) => s.v[x] = s.v[x].wrapping_add(kk),
+        (0x8, _, _, op) => {
+            let (vx, vy) = (s.v[x], s.v[y]);
+            // Result first, then the flag, which wins when x is F
+            let (result, flag) = match op {
+                0x0 => (vy, None),
+                0x1..=0x3 => {
+                    let result = match op {
+                        0x1 => vx | vy,
+                        0x2 => vx & vy,
+                        _ => vx ^ vy,
+                    };
+                    (result, s.quirks.logic_resets_vf.then_some(0))
+                }
+                0x4 => {
+                    let sum = vx as u16 + vy as u16;
+                    (sum as u8, Some((sum > 0xFF) as u8))
+                }
+                0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                0x6 | 0xE => {
+                    let source = if s.quirks.shift_uses_vy { vy } else { vx };
+                    if op == 0x6 {
+                        (source / 2, Some(source % 2))
+                    } else {
+                        (source.wrapping_mul(2), Some(source / 0x80))
+                    }
+                }
+                _ => return Err(unknown),
+            };
+            s.v[x] = result;
+            if let Some(flag) = flag {
+                s.v[0xF] = flag;
+            }
+        }
+        (0x9, _, _, 0x0) => skip(s, s.v[x] != s.v[y])?,
+        (0xA, ..) => s.i = nnn,
+        (0xB, ..) => {
+            let reg = if s.quirks.jump_uses_vx { x } else { 0 };
+            s.program_counter = nnn + s.v[reg] as u16;
+        }
+        (0xC, ..) => s.v[x] = random & kk,
+        (0xD, _, _, n) => draw(s, s.v[x] as usize, s.v[y] as usize, n as usize)?,
+        (0xE, _, 0x9, 0xE) => skip(s, s.keys[(s.v[x] & 0xF) as usize])?,
+        (0xE, _, 0xA, 0x1) => skip(s, !s.keys[(s.v[x] & 0xF) as usize])?,
+        (0xF, 0x0, 0x0, 0x0) if xo_chip => {
+            let pc = s.program_counter as usize;
+            check(s, pc, 2)?;
+            s.i = u16::from_be_bytes([s.memory[pc], s.memory[pc + 1]]);
+            s.program_counter = s.program_counter.wrapping_add(2);
+        }
+        (0xF, n, 0x0, 0x1) if xo_chip => s.planes = n & 0b11,
+        (0xF, 0x0, 0x0, 0x2) if xo_chip => {
+            let start = s.i as usize;
+            check(s, start, AUDIO_PATTERN_SIZE)?;
+            s.audio_pattern
+                .copy_from_slice(&s.memory[start..start + AUDIO_PATTERN_SIZE]);
+        }
+        (0xF, _, 0x0, 0x7) => s.v[x] = s.delay_timer,
+        (0xF, _, 0x0, 0xA) => {
+            // Waits for a key to go down and come back up, re-running itself
+            let pressed = Chip8Key::ALL.into_iter().find(|&key| s.keys[key as usize]);
+            match s.key_wait {
+                Some(key) if !s.keys[key as usize] => {
+                    s.v[x] = key as u8;
+                    s.key_wait = None;
+                }
+                waiting => {
+                    s.key_wait = waiting.or(pressed);
+                    s.program_counter = s.program_counter.wrapping_sub(2);
+                    return Ok(StepOutcome::WaitingForKey);
+                }
+            }
+        }
+        (0xF, _, 0x1, 0x5) => s.delay_timer = s.v[x],
+        (0xF, _, 0x1, 0x8) => s.sound_timer = s.v[x],
+        (0xF, _, 0x1, 0xE) => s.i = s.i.wrapping_add(s.v[x] as u16),
+        (0xF, _, 0x2, 0x9) => s.i = s.v[x] as u16 * SMALL_FONT_HEIGHT,
+        (0xF, _, 0x3, 0x0) => s.i = BIG_FONT_ADDRESS + (s.v[x] % 16) as u16 * BIG_FONT_HEIGHT,
+        (0xF, _, 0x3, 0x3) => {
+            let start = s.i as usize;
+            check(s, start, 3)?;
+            let value = s.v[x];
+            s.memory[start] = value / 100;
+            s.memory[start + 1] = value / 10 % 10;
+            s.memory[start + 2] = value % 10;
+        }
+        (0xF, _, 0x3, 0xA) if xo_chip => s.pitch = s.v[x],
+        (0xF, _, 0x5, 0x5) | (0xF, _, 0x6, 0x5) => {
+            let start = s.i as usize;
+            check(s, start, x + 1)?;
+            for reg in 0..=x {
+                if nibbles.2 == 0x5 {
+                    s.memory[start + reg] = s.v[reg];
+                } else {
+                    s.v[reg] = s.memory[start + reg];
+                }
+            }
+            if s.quirks.load_store_increments_i {
+                s.i = s.i.wrapping_add(x as u16 + 1);
+            }
+        }
+        (0xF, _, 0x7 | 0x8, 0x5) if x > 7 && !xo_chip => return Err(unknown),
+        (0xF, _, 0x7, 0x5) => {
+            for reg in 0..=x {
+                s.rpl[reg] = s.v[reg];
+            }
+        }
+        (0xF, _, 0x8, 0x5) => {
+            for reg in 0..=x {
+                s.v[reg] = s.rpl[reg];
+            }
+        }
+        _ => return Err(unknown),
+    }
+    Ok(StepOutcome::Continue)
+}
+
+fn check(s: &Snapshot, start: usize, len: usize) -> Result<(), CpuError> {
+    match (start..start + len).find(|&addr| addr >= s.memory.len()) {
+        Some(addr) => Err(CpuError::MemoryOutOfBounds { addr }),
+        None => Ok(()),
+    }
+}
+
+// Skips the next instruction, which on XO-CHIP may be a four byte F000 NNNN
+fn skip(s: &mut Snapshot, condition: bool) -> Result<(), CpuError> {
+    if !condition {
+        return Ok(());
+    }
+    let mut len = 2;
+    if s.mode == Mode::XoChip {
+        let pc = s.program_counter as usize;
+        check(s, pc, 2)?;
+        if s.memory[pc] == 0xF0 && s.memory[pc + 1] == 0x00 {
+            len = 4;
+        }
+    }
+    s.program_counter = s.program_counter.wrapping_add(len);
+    Ok(())
+}
+
+fn screen_size(s: &Snapshot) -> (usize, usize) {
+    if s.hires {
+        (SCHIP_WIDTH, SCHIP_HEIGHT)
+    } else {
+        (CHIP8_WIDTH, CHIP8_HEIGHT)
+    }
+}
+
+// Moves the selected planes' pixels by (dx, dy) within the visible area
+fn scroll(s: &mut Snapshot, dx: isize, dy: isize) {
+    let (width, height) = screen_size(s);
+    let before = s.pixels.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let (from_x, from_y) = (x as isize - dx, y as isize - dy);
+            let inside = from_x >= 0
+                && from_y >= 0
+                && (from_x as usize) < width
+                && (from_y as usize) < height;
+            let moved = if inside {
+                before[from_y as usize * SCHIP_WIDTH + from_x as usize] & s.planes
+            } else {
+                0
+            };
+            let pixel = &mut s.pixels[y * SCHIP_WIDTH + x];
+            *pixel = (*pixel & !s.planes) | moved;
+        }
+    }
+}
+
+// Dxyn draws an 8xn sprite, Dxy0 a 16x16 one, once per selected plane with
+// each plane's image following the previous one in memory
+fn draw(s: &mut Snapshot, x: usize, y: usize, n: usize) -> Result<(), CpuError> {
+    let (width, height) = screen_size(s);
+    let (sprite_width, rows, plane_bytes) = if n == 0 { (16, 16, 32) } else { (8, n, n) };
+    let planes: Vec<u8> = [1, 2].into_iter().filter(|p| s.planes & p != 0).collect();
+    let start = s.i as usize;
+    check(s, start, plane_bytes * planes.len())?;
+
+    let (x, y) = (x % width, y % height);
+    let mut collision = false;
+    for (index, plane) in planes.into_iter().enumerate() {
+        let data = start + index * plane_bytes;
+        for row in 0..rows {
+            for column in 0..sprite_width {
+                let byte = s.memory[data + row * (sprite_width / 8) + column / 8];
+                if byte & (0x80 >> (column % 8)) == 0 {
+                    continue;
+                }
+                let (px, py) = (x + column, y + row);
+                if s.quirks.clip_sprites && (px >= width || py >= height) {
+                    continue;
+                }
+                let pixel = &mut s.pixels[(py % height) * SCHIP_WIDTH + px % width];
+                collision |= *pixel & plane != 0;
+                *pixel ^= plane;
+            }
+        }
+    }
+    s.v[0xF] = collision as u8;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::chip::Chip8;
+    use crate::quirks::Quirks;
+    use crate::random::{Random, RngState};
+    use crate::snapshot::SCREEN_PIXELS;
+
+    // A generated machine and the opcode to run on it. Debug prints the
+    // parts that matter rather than kilobytes of memory, so a shrunk failure
+    // reads as a minimal reproduction.
+    #[derive(Clone)]
+    struct Case {
+        state: Snapshot,
+        opcode: u16,
+    }
+
+    impl fmt::Debug for Case {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let s = &self.state;
+            let around = |addr: usize| {
+                let end = (addr + 4).min(s.memory.len());
+                let bytes = s.memory.get(addr..end).unwrap_or(&[]);
+                format!("{:02X?}", bytes)
+            };
+            writeln!(
+                f,
+                "opcode {:04X}, {} mode, quirks {}",
+                self.opcode, s.mode, s.quirks
+            )?;
+            writeln!(f, "  V  {:02X?}", s.v)?;
+            writeln!(
+                f,
+                "  I {:04X} -> {}, PC {:04X} -> {}",
+                s.i,
+                around(s.i as usize),
+                s.program_counter,
+                around(s.program_counter as usize)
+            )?;
+            writeln!(
+                f,
+                "  SP {} stack {:03X?}, DT {} ST {}",
+                s.stack_pointer, s.stack, s.delay_timer, s.sound_timer
+            )?;
+            let pressed: Vec<usize> = (0..16).filter(|&key| s.keys[key]).collect();
+            write!(
+                f,
+                "  hires {}, planes {}, keys down {:X?}, key wait {:?}, rng {:X?}",
+                s.hires, s.planes, pressed, s.key_wait, s.rng
+            )
+        }
+    }
+
+    // Every quirk profile the frontends offer, plus all quirks off
+    fn quirk_profile() -> impl Strategy<Value = Quirks> {
+        let mut profiles = vec![Quirks::default()];
+        profiles.extend(Quirks::presets().map(|(_, quirks)| quirks));
+        prop::sample::select(profiles)
+    }
+
+    // Bulk state comes from a seed: shrinking it isn't meaningful, but it
+    // keeps generation fast even for 64 KiB of XO-CHIP memory
+    fn fill(seed: u64, len: usize, mask: u8) -> Vec<u8> {
+        let mut x = seed | 1;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8 & mask
+            })
+            .collect()
+    }
+
+    prop_compose! {
+        fn machine()(
+            quirks in quirk_profile(),
+            xo_chip in any::<bool>(),
+            memory_seed in any::<u64>(),
+            pixel_seed in any::<u64>(),
+            v in any::<[u8; 16]>(),
+            i_within in any::<bool>(),
+            i in any::<u16>(),
+            pc in any::<u16>(),
+            stack_pointer in 0..=STACK_DEPTH,
+            stack in any::<[u16; 16]>(),
+            timers in any::<(u8, u8)>(),
+            rpl in any::<[u8; 16]>(),
+            pitch in any::<u8>(),
+            key_wait in prop::option::of(0..16u8),
+            hires in any::<bool>(),
+            planes in 0..4u8,
+            keys in any::<[bool; 16]>(),
+            rng in any::<u64>().prop_map(RngState::SplitMix),
+        ) -> Snapshot {
+            let mode = if xo_chip { Mode::XoChip } else { Mode::Chip8 };
+            let memory = fill(memory_seed, mode.memory_capacity(), 0xFF);
+            // XO-CHIP's 64 KiB covers every address already
+            let len = memory.len();
+            let within = |addr: u16| (addr as usize % len) as u16;
+            Snapshot {
+                mode,
+                quirks,
+                memory,
+                v,
+                // Mostly inside memory, or nearly every load and store faults
+                i: if i_within { within(i) } else { i },
+                delay_timer: timers.0,
+                sound_timer: timers.1,
+                program_counter: within(pc),
+                stack_pointer,
+                stack,
+                rpl,
+                audio_pattern: [0; AUDIO_PATTERN_SIZE],
+                pitch,
+                key_wait: key_wait.and_then(Chip8Key::from_value),
+                hires,
+                // Only plane 1 outside XO-CHIP, where nothing can select another
+                planes: if xo_chip { planes } else { 1 },
+                pixels: fill(pixel_seed, SCREEN_PIXELS, if xo_chip { 0b11 } else { 0b01 }),
+                keys,
+                rng: Some(rng),
+            }
+        }
+    }
+
+    // Names of the fields that differ, with the first differing addresses
+    fn differences(actual: &Snapshot, expected: &Snapshot) -> Vec<String> {
+        let mut out = Vec::new();
+        macro_rules! compare {
+            ($($field:ident),*) => {
+                $(if actual.$field != expected.$field {
+                    out.push(format!(
+                        "{}: got {:X?}, expected {:X?}",
+                        stringify!($field),
+                        actual.$field,
+                        expected.$field
+                    ));
+                })*
+            };
+        }
+        compare!(
+            mode,
+            quirks,
+            v,
+            i,
+            delay_timer,
+            sound_timer,
+            program_counter,
+            stack_pointer,
+            stack,
+            rpl,
+            audio_pattern,
+            pitch,
+            key_wait,
+            hires,
+            planes,
+            keys,
+            rng
+        );
+        for (name, a, b) in [
+            ("memory", &actual.memory, &expected.memory),
+            ("pixels", &actual.pixels, &expected.pixels),
+        ] {
+            let diffs: Vec<String> = (0..a.len().max(b.len()))
+                .filter(|&n| a.get(n) != b.get(n))
+                .take(8)
+                .map(|n| format!("[{:X}] got {:X?}, expected {:X?}", n, a.get(n), b.get(n)))
+                .collect();
+            if !diffs.is_empty() {
+                out.push(format!("{}: {}", name, diffs.join(", ")));
+            }
+        }
+        out
+    }
+
+    fn check_against_reference(case: &Case) -> Result<(), TestCaseError> {
+        let mut chip8 = Chip8::new();
+        chip8.set_mode(case.state.mode);
+        chip8.restore(&case.state);
+        let actual = chip8.exec(case.opcode);
+        let after = chip8.snapshot();
+
+        // The model has no RNG of its own, it gets the byte the machine's
+        // generator will produce next and expects Cxkk to have used it up
+        let mut rng = Random::from(case.state.rng.unwrap());
+        let random = rng.next_byte();
+        let mut expected_state = case.state.clone();
+        let expected = exec(&mut expected_state, case.opcode, random);
+        if expected.is_ok() && case.opcode >> 12 == 0xC {
+            expected_state.rng = rng.state();
+        }
+
+        prop_assert_eq!(actual, expected, "outcome differs for {:?}", case);
+        let diffs = differences(&after, &expected_state);
+        prop_assert!(
+            diffs.is_empty(),
+            "state differs for {:?}\n{}",
+            case,
+            diffs.join("\n")
+        );
+        Ok(())
+    }
+
+    fn case(opcode: impl Strategy<Value = u16>) -> impl Strategy<Value = Case> {
+        (machine(), opcode).prop_map(|(state, opcode)| Case { state, opcode })
+    }
+
+    // The families whose low nibbles select between several instructions
+    fn family_opcode() -> impl Strategy<Value = u16> {
+        let families = vec![0x0000u16, 0x5000, 0x8000, 0xD000, 0xE000, 0xF000];
+        (prop::sample::select(families), 0..0x1000u16).prop_map(|(high, low)| high | low)
+    }
+
+    // More than proptest's default, PROPTEST_CASES still wins for long runs
+    fn config() -> ProptestConfig {
+        let cases = std::env::var("PROPTEST_CASES")
+            .ok()
+            .and_then(|cases| cases.parse().ok())
+            .unwrap_or(2048);
+        ProptestConfig::with_cases(cases)
+    }
+
+    proptest! {
+        #![proptest_config(config())]
+
+        #[test]
+        fn test_exec_matches_reference(case in case(any::<u16>())) {
+            check_against_reference(&case)?;
+        }
+
+        #[test]
+        fn test_opcode_families_match_reference(case in case(family_opcode())) {
+            check_against_reference(&case)?;
+        }
+    }
+}
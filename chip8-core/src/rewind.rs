@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+
+use crate::chip::Chip8;
+use crate::snapshot::Snapshot;
+
+// Going back in time, one recorded frame at a time.
+//
+// Only the newest state is kept whole, as serialized snapshot bytes. Every
+// older frame is stored as the XOR of its bytes with the frame after it, run
+// length encoded. From one frame to the next almost all of memory and the
+// screen is unchanged, so the deltas are mostly zeros and shrink to a few
+// bytes each. Rewinding XORs the newest delta back in, which reconstructs the
+// previous frame and makes it the newest state.
+pub struct RewindBuffer {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Delta>,
+    delta_bytes: usize,
+}
+
+struct Delta {
+    // Length of the older state, XO-CHIP and CHIP-8 states differ in size
+    len: usize,
+    runs: Vec<u8>,
+}
+
+impl RewindBuffer {
+    // `capacity` is the number of frames that can be stepped back
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            latest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // How many frames back the buffer currently reaches
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    // Approximate heap usage, for keeping an eye on the cost of long buffers
+    pub fn memory_usage(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.delta_bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.delta_bytes = 0;
+    }
+
+    // Records the machine as it is now, normally once per frame
+    pub fn push(&mut self, chip8: &Chip8) {
+        let state = chip8.snapshot().to_bytes();
+        if let Some(previous) = self.latest.take() {
+            if self.capacity == 0 {
+                self.latest = Some(state);
+                return;
+            }
+            if self.deltas.len() == self.capacity {
+                if let Some(oldest) = self.deltas.pop_front() {
+                    self.delta_bytes -= oldest.runs.len();
+                }
+            }
+            let delta = Delta {
+                len: previous.len(),
+                runs: encode_runs(&xor(&previous, &state)),
+            };
+            self.delta_bytes += delta.runs.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state);
+    }
+
+    // Steps one recorded frame back, restoring it into `chip8`. Returns false,
+    // leaving the machine alone, once there is no older frame.
+    pub fn rewind(&mut self, chip8: &mut Chip8) -> bool {
+        let (Some(latest), Some(delta)) = (self.latest.as_mut(), self.deltas.pop_back()) else {
+            return false;
+        };
+        self.delta_bytes -= delta.runs.len();
+        latest.resize(latest.len().max(delta.len), 0);
+        decode_runs_into(&delta.runs, latest);
+        latest.truncate(delta.len);
+
+        // The bytes came from to_bytes, so they can only fail to parse if
+        // this module has a bug
+        let snapshot = Snapshot::from_bytes(latest).expect("rewind buffer holds a corrupt state");
+        chip8.restore(&snapshot);
+        true
+    }
+}
+
+// XOR of two states, the shorter one padded with zeros
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|n| a.get(n).copied().unwrap_or(0) ^ b.get(n).copied().unwrap_or(0))
+        .collect()
+}
+
+// A delta is a sequence of (u16 zero count, u16 literal count, literals),
+// little endian, covering the whole XORed state
+fn encode_runs(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let zeros = data[pos..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|&&byte| byte == 0)
+            .count();
+        pos += zeros;
+        let literals = data[pos..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|&&byte| byte != 0)
+            .count();
+        out.extend_from_slice(&(zeros as u16).to_le_bytes());
+        out.extend_from_slice(&(literals as u16).to_le_bytes());
+        out.extend_from_slice(&data[pos..pos + literals]);
+        pos += literals;
+    }
+    out
+}
+
+// XORs an encoded delta into `target`
+fn decode_runs_into(runs: &[u8], target: &mut [u8]) {
+    let mut pos = 0;
+    let mut input = runs;
+    while input.len() >= 4 {
+        let zeros = u16::from_le_bytes([input[0], input[1]]) as usize;
+        let literals = u16::from_le_bytes([input[2], input[3]]) as usize;
+        pos += zeros;
+        for (byte, delta) in target[pos..pos + literals].iter_mut().zip(&input[4..]) {
+            *byte ^= delta;
+        }
+        pos += literals;
+        input = &input[4 + literals..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+    use crate::quirks::Quirks;
+
+    // 6A05 A20A D015 7001 1204 - draw, move right, loop
+    const MOVING_SPRITE: [u8; 11] = [
+        0x6A, 0x05, 0xA2, 0x0A, 0xD0, 0x15, 0x70, 0x01, 0x12, 0x04, 0xF0,
+    ];
+
+    fn recorded_frames(
+        buffer: &mut RewindBuffer,
+        chip8: &mut Chip8,
+        frames: usize,
+    ) -> Vec<Snapshot> {
+        let mut history = Vec::new();
+        for _ in 0..frames {
+            chip8.run_frame(7).unwrap();
+            buffer.push(chip8);
+            history.push(chip8.snapshot());
+        }
+        history
+    }
+
+    #[test]
+    fn test_rewind_retraces_every_frame() {
+        let mut chip8 = Chip8::with_quirks(Quirks::schip());
+        chip8.load(&MOVING_SPRITE).unwrap();
+        let mut buffer = RewindBuffer::new(100);
+        let history = recorded_frames(&mut buffer, &mut chip8, 30);
+
+        for expected in history.iter().rev().skip(1) {
+            assert!(buffer.rewind(&mut chip8));
+            assert_eq!(&chip8.snapshot(), expected);
+        }
+        assert!(buffer.is_empty());
+        assert!(!buffer.rewind(&mut chip8));
+        assert_eq!(chip8.snapshot(), history[0]);
+    }
+
+    #[test]
+    fn test_rewind_depth_is_bounded() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&MOVING_SPRITE).unwrap();
+        let mut buffer = RewindBuffer::new(10);
+        let history = recorded_frames(&mut buffer, &mut chip8, 50);
+
+        assert_eq!(buffer.len(), 10);
+        while buffer.rewind(&mut chip8) {}
+        assert_eq!(chip8.snapshot(), history[39]);
+    }
+
+    #[test]
+    fn test_deltas_are_compact() {
+        let mut chip8 = Chip8::new();
+        chip8.set_mode(Mode::XoChip);
+        chip8.load(&MOVING_SPRITE).unwrap();
+        let mut buffer = RewindBuffer::new(100);
+        recorded_frames(&mut buffer, &mut chip8, 100);
+
+        // A full XO-CHIP state is over 72 KiB, a hundred of them would be 7 MiB
+        let full_state = chip8.snapshot().to_bytes().len();
+        assert!(buffer.memory_usage() < 2 * full_state);
+    }
+
+    #[test]
+    fn test_rewind_across_mode_change() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&MOVING_SPRITE).unwrap();
+        let mut buffer = RewindBuffer::new(10);
+        buffer.push(&chip8);
+        let before = chip8.snapshot();
+        chip8.set_mode(Mode::XoChip);
+        buffer.push(&chip8);
+
+        assert!(buffer.rewind(&mut chip8));
+        assert_eq!(chip8.snapshot(), before);
+    }
+}
@@ -0,0 +1,262 @@
+// Low resolution is the original CHIP-8 display, high resolution is the
+// SUPER-CHIP extended mode
+pub const CHIP8_WIDTH: usize = 64;
+pub const CHIP8_HEIGHT: usize = 32;
+pub const SCHIP_WIDTH: usize = 128;
+pub const SCHIP_HEIGHT: usize = 64;
+// XO-CHIP has two bitplanes, so every pixel is one of four colors. Each
+// pixel stores one bit per plane, plane 1 being the lowest bit.
+pub const TOTAL_PLANES: u8 = 2;
+const ALL_PLANES: u8 = 0b11;
+const ASCII_PIXELS: [char; 4] = ['.', '#', '+', '%'];
+// Black and white, with grays for the XO-CHIP plane 2 and both-planes colors
+pub const DEFAULT_COLORS: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x00],
+    [0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55],
+];
+
+pub struct Screen {
+    pub(crate) hires: bool,
+    pub(crate) planes: u8,
+    pub(crate) pixels: [[u8; SCHIP_WIDTH]; SCHIP_HEIGHT],
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self {
+            hires: false,
+            planes: 1,
+            pixels: [[0; SCHIP_WIDTH]; SCHIP_HEIGHT],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires {
+            SCHIP_WIDTH
+        } else {
+            CHIP8_WIDTH
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires {
+            SCHIP_HEIGHT
+        } else {
+            CHIP8_HEIGHT
+        }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    // Switching resolution clears the display, on every plane
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        for row in &mut self.pixels {
+            for pixel in row {
+                *pixel = 0;
+            }
+        }
+    }
+
+    // Fn01 - bitmask of the planes that drawing, clearing and scrolling affect
+    pub fn selected_planes(&self) -> u8 {
+        self.planes
+    }
+
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ALL_PLANES;
+    }
+
+    pub fn selected_plane_count(&self) -> usize {
+        self.planes.count_ones() as usize
+    }
+
+    fn selected_plane_bits(&self) -> impl Iterator<Item = u8> {
+        let planes = self.planes;
+        (0..TOTAL_PLANES)
+            .map(|plane| 1 << plane)
+            .filter(move |bit| planes & bit != 0)
+    }
+
+    pub fn check_bounds(&self, x: usize, y: usize) {
+        assert!(
+            x < self.width() && y < self.height(),
+            "Pixel coordinates out of bounds"
+        );
+    }
+
+    pub fn set_screen(&mut self, x: usize, y: usize) {
+        self.check_bounds(x, y);
+        self.pixels[y][x] |= self.planes;
+    }
+
+    // Clears the selected planes
+    pub fn clear(&mut self) {
+        let keep = !self.planes;
+        for row in &mut self.pixels {
+            for pixel in row {
+                *pixel &= keep;
+            }
+        }
+    }
+
+    pub fn is_set(&self, x: usize, y: usize) -> bool {
+        self.check_bounds(x, y);
+        self.pixels[y][x] != 0
+    }
+
+    // Color index of a pixel, one bit per plane
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.check_bounds(x, y);
+        self.pixels[y][x]
+    }
+
+    // One character per pixel and a newline per row: '.' off, '#' plane 1,
+    // and for XO-CHIP '+' plane 2 only and '%' both planes
+    pub fn to_ascii(&self) -> String {
+        let mut out = String::with_capacity((self.width() + 1) * self.height());
+        for row in &self.pixels[..self.height()] {
+            for &pixel in &row[..self.width()] {
+                out.push(ASCII_PIXELS[pixel as usize]);
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    // Packed 8-bit RGB, three bytes per pixel, coloring each pixel by its
+    // plane bits
+    pub fn to_rgb(&self, colors: &[[u8; 3]; 4]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.width() * self.height() * 3);
+        for row in &self.pixels[..self.height()] {
+            for &pixel in &row[..self.width()] {
+                out.extend_from_slice(&colors[pixel as usize]);
+            }
+        }
+        out
+    }
+
+    // Scrolls the selected planes so that the pixel at (x, y) ends up at
+    // (x + dx, y + dy). Pixels scrolled in from outside the screen are off.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let planes = self.planes;
+        let source = self.pixels;
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&sx) && (0..height).contains(&sy) {
+                    source[sy as usize][sx as usize] & planes
+                } else {
+                    0
+                };
+                let pixel = &mut self.pixels[y as usize][x as usize];
+                *pixel = (*pixel & !planes) | moved;
+            }
+        }
+    }
+
+    // 00CN - scroll the display down by n pixels
+    pub fn scroll_down(&mut self, n: usize) {
+        self.scroll(0, n as isize);
+    }
+
+    // 00DN - scroll the display up by n pixels (XO-CHIP)
+    pub fn scroll_up(&mut self, n: usize) {
+        self.scroll(0, -(n as isize));
+    }
+
+    // 00FB - scroll the display right by n pixels
+    pub fn scroll_right(&mut self, n: usize) {
+        self.scroll(n as isize, 0);
+    }
+
+    // 00FC - scroll the display left by n pixels
+    pub fn scroll_left(&mut self, n: usize) {
+        self.scroll(-(n as isize), 0);
+    }
+
+    pub fn chip8_screen_draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        num: usize,
+        clip: bool,
+    ) -> bool {
+        if num == 0 {
+            return false;
+        }
+        // With several planes selected the sprite holds one `num` byte image per plane
+        let mut pixel_collision = false;
+        let planes: Vec<u8> = self.selected_plane_bits().collect();
+        for (plane, data) in planes.into_iter().zip(sprite.chunks(num)) {
+            let rows = data.iter().map(|&c| (c as u16) << 8);
+            pixel_collision |= self.draw_rows(x, y, rows, 8, plane, clip);
+        }
+        pixel_collision
+    }
+
+    // Dxy0 - SUPER-CHIP 16x16 sprite, two bytes per row
+    pub fn draw_large_sprite(&mut self, x: usize, y: usize, sprite: &[u8], clip: bool) -> bool {
+        let mut pixel_collision = false;
+        let planes: Vec<u8> = self.selected_plane_bits().collect();
+        for (plane, data) in planes.into_iter().zip(sprite.chunks(32)) {
+            let rows = data
+                .chunks(2)
+                .map(|row| ((row[0] as u16) << 8) | row[1] as u16);
+            pixel_collision |= self.draw_rows(x, y, rows, 16, plane, clip);
+        }
+        pixel_collision
+    }
+
+    // Each row is left aligned in a u16, `width` is how many of its bits to draw
+    fn draw_rows(
+        &mut self,
+        x: usize,
+        y: usize,
+        rows: impl Iterator<Item = u16>,
+        width: usize,
+        plane: u8,
+        clip: bool,
+    ) -> bool {
+        let (screen_width, screen_height) = (self.width(), self.height());
+        let mut pixel_collision = false;
+        // The starting position always wraps, only the sprite body is clipped
+        let x = x % screen_width;
+        let y = y % screen_height;
+
+        for (ly, c) in rows.enumerate() {
+            for lx in 0..width {
+                if (c & (0x8000 >> lx)) == 0 {
+                    continue;
+                }
+
+                if clip && (x + lx >= screen_width || y + ly >= screen_height) {
+                    continue;
+                }
+
+                let screen_x = (x + lx) % screen_width;
+                let screen_y = (y + ly) % screen_height;
+
+                if self.pixels[screen_y][screen_x] & plane != 0 {
+                    pixel_collision = true;
+                }
+
+                self.pixels[screen_y][screen_x] ^= plane;
+            }
+        }
+
+        pixel_collision
+    }
+}
@@ -0,0 +1,453 @@
+use std::fmt;
+
+use crate::checksum::crc32;
+use crate::chip::{
+    Chip8, AUDIO_PATTERN_SIZE, TOTAL_DATA_REGISTERS, TOTAL_RPL_FLAGS, TOTAL_STACK_DEPTH,
+};
+use crate::keyboard::{Chip8Key, TOTAL_KEYS};
+use crate::mode::Mode;
+use crate::quirks::Quirks;
+use crate::random::{Random, RngState};
+use crate::screen::{SCHIP_HEIGHT, SCHIP_WIDTH};
+
+// On-disk layout, all integers little endian:
+//
+//   magic "C8SS", u16 version
+//   u8 mode, u8 quirk bits
+//   u32 memory length, memory
+//   V0-VF, u16 I, u8 delay timer, u8 sound timer, u16 PC, u8 SP, 16 x u16 stack
+//   16 RPL flags, 16 byte audio pattern, u8 pitch, u8 Fx0A key (0xFF if none)
+//   u8 hires, u8 selected planes, 128x64 pixels
+//   u16 keypad bitmask
+//   u8 RNG kind: 0 not saved, 1 SplitMix then u64 state (since version 2). 2
+//   was a VIP-style generator, since removed.
+//   u32 CRC-32 of everything before it
+pub const SNAPSHOT_MAGIC: &[u8; 4] = b"C8SS";
+pub const SNAPSHOT_VERSION: u16 = 2;
+// Version 1 is the same without the RNG
+const SNAPSHOT_VERSION_NO_RNG: u16 = 1;
+const NO_KEY: u8 = 0xFF;
+pub(crate) const SCREEN_PIXELS: usize = SCHIP_WIDTH * SCHIP_HEIGHT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "not a CHIP-8 save state"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            SnapshotError::ChecksumMismatch => write!(f, "save state is corrupted (bad checksum)"),
+            SnapshotError::Truncated => write!(f, "save state is truncated"),
+            SnapshotError::Invalid(what) => write!(f, "invalid save state: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+// The complete machine at one instant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub(crate) mode: Mode,
+    pub(crate) quirks: Quirks,
+    pub(crate) memory: Vec<u8>,
+    pub(crate) v: [u8; TOTAL_DATA_REGISTERS],
+    pub(crate) i: u16,
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    pub(crate) program_counter: u16,
+    pub(crate) stack_pointer: u8,
+    pub(crate) stack: [u16; TOTAL_STACK_DEPTH as usize],
+    pub(crate) rpl: [u8; TOTAL_RPL_FLAGS],
+    pub(crate) audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub(crate) pitch: u8,
+    pub(crate) key_wait: Option<Chip8Key>,
+    pub(crate) hires: bool,
+    pub(crate) planes: u8,
+    // Row major, SCHIP_WIDTH pixels per row whatever the active resolution
+    pub(crate) pixels: Vec<u8>,
+    pub(crate) keys: [bool; TOTAL_KEYS],
+    // None when the machine uses a custom RNG, restoring keeps the current one
+    pub(crate) rng: Option<RngState>,
+}
+
+impl Chip8 {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            mode: self.mode,
+            quirks: self.quirks,
+            memory: self.memory.clone(),
+            v: self.v,
+            i: self.i,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            program_counter: self.program_counter,
+            stack_pointer: self.stack_pointer,
+            stack: self.stack,
+            rpl: self.rpl,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            key_wait: self.key_wait,
+            hires: self.screen.hires,
+            planes: self.screen.planes,
+            pixels: self.screen.pixels.concat(),
+            keys: self.keyboard.keys,
+            rng: self.rng.state(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.mode = snapshot.mode;
+        self.quirks = snapshot.quirks;
+        self.memory.clone_from(&snapshot.memory);
+        self.v = snapshot.v;
+        self.i = snapshot.i;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.program_counter = snapshot.program_counter;
+        self.stack_pointer = snapshot.stack_pointer;
+        self.stack = snapshot.stack;
+        self.rpl = snapshot.rpl;
+        self.audio_pattern = snapshot.audio_pattern;
+        self.pitch = snapshot.pitch;
+        self.key_wait = snapshot.key_wait;
+        self.screen.hires = snapshot.hires;
+        self.screen.planes = snapshot.planes;
+        for (row, pixels) in self
+            .screen
+            .pixels
+            .iter_mut()
+            .zip(snapshot.pixels.chunks(SCHIP_WIDTH))
+        {
+            row.copy_from_slice(pixels);
+        }
+        self.keyboard.keys = snapshot.keys;
+        if let Some(state) = snapshot.rng {
+            self.rng = Random::from(state);
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + SCREEN_PIXELS + 128);
+        out.extend_from_slice(SNAPSHOT_MAGIC);
+        out.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        out.push(match self.mode {
+            Mode::Chip8 => 0,
+            Mode::XoChip => 1,
+        });
+        out.push(self.quirks.to_bits());
+        out.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.memory);
+        out.extend_from_slice(&self.v);
+        out.extend_from_slice(&self.i.to_le_bytes());
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.extend_from_slice(&self.program_counter.to_le_bytes());
+        out.push(self.stack_pointer);
+        for entry in self.stack {
+            out.extend_from_slice(&entry.to_le_bytes());
+        }
+        out.extend_from_slice(&self.rpl);
+        out.extend_from_slice(&self.audio_pattern);
+        out.push(self.pitch);
+        out.push(self.key_wait.map_or(NO_KEY, Chip8Key::value));
+        out.push(self.hires as u8);
+        out.push(self.planes);
+        out.extend_from_slice(&self.pixels);
+        let keys = self
+            .keys
+            .iter()
+            .enumerate()
+            .fold(0u16, |mask, (n, &down)| mask | ((down as u16) << n));
+        out.extend_from_slice(&keys.to_le_bytes());
+        match self.rng {
+            None => out.push(0),
+            Some(RngState::SplitMix(state)) => {
+                out.push(1);
+                out.extend_from_slice(&state.to_le_bytes());
+            }
+        }
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Snapshot, SnapshotError> {
+        if data.len() < SNAPSHOT_MAGIC.len() + 2 {
+            return Err(SnapshotError::Truncated);
+        }
+        if &data[..4] != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != SNAPSHOT_VERSION && version != SNAPSHOT_VERSION_NO_RNG {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        if data.len() < 10 {
+            return Err(SnapshotError::Truncated);
+        }
+        let (body, checksum) = data.split_at(data.len() - 4);
+        if crc32(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(SnapshotError::ChecksumMismatch);
+        }
+
+        let mut reader = Reader { data: body, pos: 6 };
+        let mode = match reader.u8()? {
+            0 => Mode::Chip8,
+            1 => Mode::XoChip,
+            _ => return Err(SnapshotError::Invalid("unknown mode")),
+        };
+        let quirks = Quirks::from_bits(reader.u8()?);
+        let memory_len = reader.u32()? as usize;
+        if memory_len != mode.memory_capacity() {
+            return Err(SnapshotError::Invalid("memory size doesn't match the mode"));
+        }
+        let memory = reader.bytes(memory_len)?.to_vec();
+        let v = reader.array()?;
+        let i = reader.u16()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let program_counter = reader.u16()?;
+        let stack_pointer = reader.u8()?;
+        if stack_pointer > TOTAL_STACK_DEPTH {
+            return Err(SnapshotError::Invalid("stack pointer out of range"));
+        }
+        let mut stack = [0; TOTAL_STACK_DEPTH as usize];
+        for entry in stack.iter_mut() {
+            *entry = reader.u16()?;
+        }
+        let rpl = reader.array()?;
+        let audio_pattern = reader.array()?;
+        let pitch = reader.u8()?;
+        let key_wait = match reader.u8()? {
+            NO_KEY => None,
+            value => {
+                Some(Chip8Key::from_value(value).ok_or(SnapshotError::Invalid("unknown key"))?)
+            }
+        };
+        let hires = reader.u8()? != 0;
+        let planes = reader.u8()?;
+        if planes > 0b11 {
+            return Err(SnapshotError::Invalid("unknown bitplanes selected"));
+        }
+        let pixels = reader.bytes(SCREEN_PIXELS)?.to_vec();
+        // Plain CHIP-8 only ever draws to the first plane
+        let max_pixel = match mode {
+            Mode::Chip8 => 0b01,
+            Mode::XoChip => 0b11,
+        };
+        if pixels.iter().any(|&pixel| pixel > max_pixel) {
+            return Err(SnapshotError::Invalid("pixel out of range"));
+        }
+        let mask = reader.u16()?;
+        let mut keys = [false; TOTAL_KEYS];
+        for (n, key) in keys.iter_mut().enumerate() {
+            *key = mask & (1 << n) != 0;
+        }
+        let rng = if version == SNAPSHOT_VERSION_NO_RNG {
+            None
+        } else {
+            match reader.u8()? {
+                0 => None,
+                1 => Some(RngState::SplitMix(reader.u64()?)),
+                2 => {
+                    return Err(SnapshotError::Invalid(
+                        "the VIP-style RNG is no longer supported",
+                    ))
+                }
+                _ => return Err(SnapshotError::Invalid("unknown RNG")),
+            }
+        };
+        if reader.pos != body.len() {
+            return Err(SnapshotError::Invalid("trailing data"));
+        }
+
+        Ok(Snapshot {
+            mode,
+            quirks,
+            memory,
+            v,
+            i,
+            delay_timer,
+            sound_timer,
+            program_counter,
+            stack_pointer,
+            stack,
+            rpl,
+            audio_pattern,
+            pitch,
+            key_wait,
+            hires,
+            planes,
+            pixels,
+            keys,
+            rng,
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        let end = self.pos + len;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or(SnapshotError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running_machine() -> Chip8 {
+        let mut chip8 = Chip8::with_quirks(Quirks::schip());
+        // 6A05 A20A D015 7001 1204 - draw, move right, loop
+        chip8
+            .load(&[
+                0x6A, 0x05, 0xA2, 0x0A, 0xD0, 0x15, 0x70, 0x01, 0x12, 0x04, 0xF0,
+            ])
+            .unwrap();
+        chip8.keyboard.key_down(Chip8Key::Key4);
+        chip8.delay_timer = 30;
+        for _ in 0..20 {
+            chip8.step().unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn test_snapshot_restore_round_trip() {
+        let mut chip8 = running_machine();
+        let snapshot = chip8.snapshot();
+        for _ in 0..20 {
+            chip8.step().unwrap();
+        }
+        assert_ne!(chip8.snapshot(), snapshot);
+        chip8.restore(&snapshot);
+        assert_eq!(chip8.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_bytes_round_trip() {
+        let snapshot = running_machine().snapshot();
+        let bytes = snapshot.to_bytes();
+        assert_eq!(&bytes[..4], SNAPSHOT_MAGIC);
+        assert_eq!(Snapshot::from_bytes(&bytes), Ok(snapshot));
+    }
+
+    #[test]
+    fn test_restore_replays_random_numbers() {
+        let mut chip8 = running_machine();
+        chip8.rng = Random::seeded(99);
+        let snapshot = chip8.snapshot();
+        let draw = |chip8: &mut Chip8| {
+            chip8.exec(0xC0FF).unwrap();
+            chip8.v[0]
+        };
+        let first: Vec<u8> = (0..8).map(|_| draw(&mut chip8)).collect();
+        let bytes = snapshot.to_bytes();
+        chip8.restore(&Snapshot::from_bytes(&bytes).unwrap());
+        let again: Vec<u8> = (0..8).map(|_| draw(&mut chip8)).collect();
+        assert_eq!(first, again);
+    }
+
+    #[test]
+    fn test_reads_version_1_without_rng() {
+        let mut snapshot = running_machine().snapshot();
+        snapshot.rng = None;
+        let mut bytes = snapshot.to_bytes();
+        // Drop the RNG byte and the checksum, then redo both for version 1
+        bytes.truncate(bytes.len() - 5);
+        bytes[4..6].copy_from_slice(&SNAPSHOT_VERSION_NO_RNG.to_le_bytes());
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        assert_eq!(Snapshot::from_bytes(&bytes), Ok(snapshot));
+    }
+
+    #[test]
+    fn test_snapshot_rejects_bad_files() {
+        let mut bytes = running_machine().snapshot().to_bytes();
+        assert_eq!(
+            Snapshot::from_bytes(b"PNG\0\0\0\0\0\0\0"),
+            Err(SnapshotError::BadMagic)
+        );
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::ChecksumMismatch)
+        );
+
+        let flipped = bytes.len() / 2;
+        bytes[flipped] ^= 0xFF;
+        assert_eq!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::ChecksumMismatch)
+        );
+
+        bytes[4] = 99;
+        assert_eq!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::UnsupportedVersion(99))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_rejects_pixels_out_of_range() {
+        let mut snapshot = running_machine().snapshot();
+        snapshot.pixels[0] = 0b10;
+        assert_eq!(
+            Snapshot::from_bytes(&snapshot.to_bytes()),
+            Err(SnapshotError::Invalid("pixel out of range"))
+        );
+
+        snapshot.mode = Mode::XoChip;
+        snapshot.memory.resize(Mode::XoChip.memory_capacity(), 0);
+        assert_eq!(
+            Snapshot::from_bytes(&snapshot.to_bytes()),
+            Ok(snapshot.clone())
+        );
+        snapshot.pixels[0] = 4;
+        assert_eq!(
+            Snapshot::from_bytes(&snapshot.to_bytes()),
+            Err(SnapshotError::Invalid("pixel out of range"))
+        );
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::screen::{Screen, SCHIP_HEIGHT, SCHIP_WIDTH};
+
+// Every frame is recorded at 128x64, with low resolution pixels doubled, so a
+// program switching resolution mid-clip doesn't change the video's size
+const FRAME_WIDTH: usize = SCHIP_WIDTH;
+const FRAME_HEIGHT: usize = SCHIP_HEIGHT;
+const FRAMES_PER_SECOND: u64 = 60;
+
+// GIF: four colors need a 2-bit palette, LZW codes grow up to 12 bits
+const GIF_MIN_CODE_SIZE: u8 = 2;
+const GIF_MAX_CODE: u16 = 4096;
+const GIF_MAX_SUB_BLOCK: usize = 255;
+// Viewers show shorter delays, 0 and 1 cs, as 10 cs
+const GIF_MIN_DELAY: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    // Animated, with identical consecutive frames merged into one
+    Gif,
+    // YUV4MPEG2, 4:4:4, for encoders such as ffmpeg
+    Y4m,
+    // Bare rgb24 frames, one after the other
+    Rgb,
+}
+
+impl VideoFormat {
+    // Picked from a file name's extension
+    pub fn from_path(path: &str) -> Option<VideoFormat> {
+        let extension = path.rsplit_once('.')?.1;
+        extension.parse().ok()
+    }
+}
+
+impl FromStr for VideoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gif" => Ok(VideoFormat::Gif),
+            "y4m" => Ok(VideoFormat::Y4m),
+            "rgb" | "raw" => Ok(VideoFormat::Rgb),
+            _ => Err(format!(
+                "Unknown video format '{}', expected gif, y4m or rgb",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for VideoFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VideoFormat::Gif => write!(f, "gif"),
+            VideoFormat::Y4m => write!(f, "y4m"),
+            VideoFormat::Rgb => write!(f, "rgb"),
+        }
+    }
+}
+
+// Records one frame of the screen per 60 Hz tick, streaming it to `out`
+pub struct VideoRecorder<W: Write> {
+    out: W,
+    format: VideoFormat,
+    colors: [[u8; 3]; 4],
+    scale: usize,
+    // GIF only: the last frame, written once it's known how long it stays up
+    pending: Option<Vec<u8>>,
+    pending_ticks: u64,
+    ticks: u64,
+}
+
+impl<W: Write> VideoRecorder<W> {
+    // Writes the header. `colors` is indexed by pixel value and every
+    // 128x64 pixel becomes a `scale` x `scale` block.
+    pub fn new(
+        mut out: W,
+        format: VideoFormat,
+        colors: &[[u8; 3]; 4],
+        scale: u32,
+    ) -> io::Result<Self> {
+        let scale = scale.max(1) as usize;
+        let (width, height) = (FRAME_WIDTH * scale, FRAME_HEIGHT * scale);
+        if format == VideoFormat::Gif && width > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "scale {} is too large for a GIF, they're at most {} pixels wide",
+                    scale,
+                    u16::MAX
+                ),
+            ));
+        }
+        match format {
+            VideoFormat::Gif => write_gif_header(&mut out, width, height, colors)?,
+            VideoFormat::Y4m => writeln!(
+                out,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, FRAMES_PER_SECOND
+            )?,
+            VideoFormat::Rgb => {}
+        }
+        Ok(VideoRecorder {
+            out,
+            format,
+            colors: *colors,
+            scale,
+            pending: None,
+            pending_ticks: 0,
+            ticks: 0,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        FRAME_WIDTH * self.scale
+    }
+
+    pub fn height(&self) -> usize {
+        FRAME_HEIGHT * self.scale
+    }
+
+    pub fn add_frame(&mut self, screen: &Screen) -> io::Result<()> {
+        let pixels = self.frame_pixels(screen);
+        match self.format {
+            VideoFormat::Gif => {
+                if self.pending.as_ref() == Some(&pixels) {
+                    self.pending_ticks += 1;
+                    return Ok(());
+                }
+                // A frame too short to show is replaced, the next one takes
+                // over its time
+                if self.pending_delay() < GIF_MIN_DELAY {
+                    self.pending = Some(pixels);
+                    self.pending_ticks += 1;
+                    return Ok(());
+                }
+                self.flush_pending()?;
+                self.pending = Some(pixels);
+                self.pending_ticks = 1;
+            }
+            VideoFormat::Y4m => {
+                self.out.write_all(b"FRAME\n")?;
+                // Planar: all of Y, then U, then V
+                for plane in 0..3 {
+                    let values: Vec<u8> = pixels
+                        .iter()
+                        .map(|&pixel| to_ycbcr(self.colors[pixel as usize])[plane])
+                        .collect();
+                    self.out.write_all(&values)?;
+                }
+            }
+            VideoFormat::Rgb => {
+                let rgb: Vec<u8> = pixels
+                    .iter()
+                    .flat_map(|&pixel| self.colors[pixel as usize])
+                    .collect();
+                self.out.write_all(&rgb)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes whatever is still buffered and the trailer, and hands back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format == VideoFormat::Gif {
+            self.flush_pending()?;
+            self.out.write_all(&[0x3B])?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    // Pixel values for the whole frame, scaled
+    fn frame_pixels(&self, screen: &Screen) -> Vec<u8> {
+        let doubling = FRAME_WIDTH / screen.width();
+        let block = doubling * self.scale;
+        let mut pixels = Vec::with_capacity(self.width() * self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                pixels.push(screen.pixel(x / block, y / block));
+            }
+        }
+        pixels
+    }
+
+    fn flush_pending(&mut self) -> io::Result<()> {
+        let Some(pixels) = self.pending.take() else {
+            return Ok(());
+        };
+        // Only the last frame can still be too short here
+        let delay = self.pending_delay().clamp(GIF_MIN_DELAY, u16::MAX as u64) as u16;
+        self.ticks += self.pending_ticks;
+        let (width, height) = (self.width(), self.height());
+        write_gif_frame(&mut self.out, width, height, &pixels, delay)
+    }
+
+    // GIF delays are in hundredths of a second. Rounding the running total
+    // rather than each frame keeps the clip in sync over time.
+    fn pending_delay(&self) -> u64 {
+        centiseconds(self.ticks + self.pending_ticks) - centiseconds(self.ticks)
+    }
+}
+
+fn centiseconds(ticks: u64) -> u64 {
+    (ticks * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND
+}
+
+// BT.601, limited range, which is what encoders assume for Y4M
+fn to_ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+    let cb = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+    let cr = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+    [y as u8, cb as u8, cr as u8]
+}
+
+fn write_gif_header(
+    out: &mut impl Write,
+    width: usize,
+    height: usize,
+    colors: &[[u8; 3]; 4],
+) -> io::Result<()> {
+    out.write_all(b"GIF89a")?;
+    out.write_all(&(width as u16).to_le_bytes())?;
+    out.write_all(&(height as u16).to_le_bytes())?;
+    // Global color table of 2^(1 + 1) entries, 2 bits of color resolution
+    out.write_all(&[0x91, 0, 0])?;
+    for color in colors {
+        out.write_all(color)?;
+    }
+    // NETSCAPE2.0 extension: loop forever
+    out.write_all(&[0x21, 0xFF, 0x0B])?;
+    out.write_all(b"NETSCAPE2.0")?;
+    out.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])
+}
+
+fn write_gif_frame(
+    out: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    delay: u16,
+) -> io::Result<()> {
+    // Graphic control extension: no transparency, just the delay
+    out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+    out.write_all(&delay.to_le_bytes())?;
+    out.write_all(&[0x00, 0x00])?;
+    // Image descriptor covering the whole screen, no local color table
+    out.write_all(&[0x2C, 0, 0, 0, 0])?;
+    out.write_all(&(width as u16).to_le_bytes())?;
+    out.write_all(&(height as u16).to_le_bytes())?;
+    out.write_all(&[0x00, GIF_MIN_CODE_SIZE])?;
+    for block in lzw_encode(pixels).chunks(GIF_MAX_SUB_BLOCK) {
+        out.write_all(&[block.len() as u8])?;
+        out.write_all(block)?;
+    }
+    out.write_all(&[0x00])
+}
+
+// Packs variable width codes least significant bit first
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+// GIF's LZW: the table starts with one code per color plus clear and end
+// codes, and is cleared when it fills up at 12 bits
+fn lzw_encode(pixels: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << GIF_MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut width = GIF_MIN_CODE_SIZE + 1;
+    let mut bits = BitWriter {
+        out: Vec::new(),
+        buffer: 0,
+        bits: 0,
+    };
+
+    bits.write(clear, width);
+    let Some((&first, rest)) = pixels.split_first() else {
+        bits.write(end, width);
+        return bits.finish();
+    };
+    let mut prefix = first as u16;
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        bits.write(prefix, width);
+        if next_code == GIF_MAX_CODE {
+            bits.write(clear, width);
+            table.clear();
+            next_code = end + 1;
+            width = GIF_MIN_CODE_SIZE + 1;
+        } else {
+            table.insert((prefix, pixel), next_code);
+            next_code += 1;
+            // The decoder adds its entries one code later, so it widens
+            // once the table holds one more code than fits
+            if next_code > 1 << width && width < 12 {
+                width += 1;
+            }
+        }
+        prefix = pixel as u16;
+    }
+    bits.write(prefix, width);
+    bits.write(end, width);
+    bits.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLORS: [[u8; 3]; 4] = [[0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 0, 255]];
+
+    // A plain GIF LZW decoder, written from the spec rather than the encoder
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let clear = 1u16 << GIF_MIN_CODE_SIZE;
+        let end = clear + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            *table = (0..clear).map(|n| vec![n as u8]).collect();
+            table.push(Vec::new());
+            table.push(Vec::new());
+        };
+        reset(&mut table);
+        let mut width = GIF_MIN_CODE_SIZE + 1;
+        let (mut buffer, mut bits, mut pos) = (0u32, 0u8, 0);
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        loop {
+            while bits < width {
+                buffer |= (data[pos] as u32) << bits;
+                pos += 1;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << width) - 1)) as u16;
+            buffer >>= width;
+            bits -= width;
+
+            if code == clear {
+                reset(&mut table);
+                width = GIF_MIN_CODE_SIZE + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    let mut entry = previous.clone();
+                    entry.push(previous[0]);
+                    entry
+                }
+                (None, None) => panic!("code {} before any entry", code),
+            };
+            if let Some(mut previous) = previous.take() {
+                previous.push(entry[0]);
+                table.push(previous);
+                if table.len() == 1 << width && width < 12 {
+                    width += 1;
+                }
+            }
+            out.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    // (delay, pixels) for every frame of a GIF written by the recorder
+    fn gif_frames(gif: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        assert_eq!(&gif[..6], b"GIF89a");
+        // Header, screen descriptor, 4 colors, NETSCAPE block
+        let mut pos = 6 + 7 + 12 + 19;
+        let mut frames = Vec::new();
+        while gif[pos] == 0x21 {
+            let delay = u16::from_le_bytes([gif[pos + 4], gif[pos + 5]]);
+            pos += 8 + 10;
+            assert_eq!(gif[pos], GIF_MIN_CODE_SIZE);
+            pos += 1;
+            let mut data = Vec::new();
+            while gif[pos] != 0 {
+                let len = gif[pos] as usize;
+                data.extend_from_slice(&gif[pos + 1..pos + 1 + len]);
+                pos += 1 + len;
+            }
+            pos += 1;
+            frames.push((delay, lzw_decode(&data)));
+        }
+        assert_eq!(gif[pos..], [0x3B]);
+        frames
+    }
+
+    #[test]
+    fn test_lzw_round_trips() {
+        let mut noise = 0x1234u32;
+        let long: Vec<u8> = (0..20000)
+            .map(|_| {
+                noise = noise.wrapping_mul(1103515245).wrapping_add(12345);
+                (noise >> 16) as u8 & 3
+            })
+            .collect();
+        for pixels in [
+            vec![],
+            vec![1],
+            vec![0; 5000],
+            vec![1, 2, 1, 2, 1, 2, 3],
+            long,
+        ] {
+            assert_eq!(lzw_decode(&lzw_encode(&pixels)), pixels);
+        }
+    }
+
+    #[test]
+    fn test_gif_merges_identical_frames() {
+        let mut screen = Screen::new();
+        let mut recorder = VideoRecorder::new(Vec::new(), VideoFormat::Gif, &COLORS, 1).unwrap();
+        for _ in 0..3 {
+            recorder.add_frame(&screen).unwrap();
+        }
+        screen.set_screen(0, 0);
+        recorder.add_frame(&screen).unwrap();
+        screen.set_hires(true);
+        screen.set_screen(127, 63);
+        for _ in 0..60 {
+            recorder.add_frame(&screen).unwrap();
+        }
+        let frames = gif_frames(&recorder.finish().unwrap());
+
+        // 3 ticks = 5cs, 1 more ends at 7cs, then a second to 107cs
+        let delays: Vec<u16> = frames.iter().map(|(delay, _)| *delay).collect();
+        assert_eq!(delays, [5, 2, 100]);
+        assert!(frames[0].1.iter().all(|&pixel| pixel == 0));
+        // The low resolution pixel is doubled up to fill 2x2
+        let lit: Vec<usize> = (0..frames[1].1.len())
+            .filter(|&n| frames[1].1[n] == 1)
+            .collect();
+        assert_eq!(lit, [0, 1, 128, 129]);
+        assert_eq!(frames[2].1[128 * 64 - 1], 1);
+    }
+
+    #[test]
+    fn test_gif_delays_are_at_least_2cs() {
+        let mut screen = Screen::new();
+        let mut recorder = VideoRecorder::new(Vec::new(), VideoFormat::Gif, &COLORS, 1).unwrap();
+        // A pixel blinking every tick, 1.67 cs per frame
+        for tick in 0..6 {
+            if tick % 2 == 0 {
+                screen.clear();
+            } else {
+                screen.set_screen(0, 0);
+            }
+            recorder.add_frame(&screen).unwrap();
+        }
+        let frames = gif_frames(&recorder.finish().unwrap());
+
+        // The frames rounding down to 1 cs are dropped, the total stays 10 cs
+        let delays: Vec<u16> = frames.iter().map(|(delay, _)| *delay).collect();
+        assert_eq!(delays, [2, 3, 2, 3]);
+    }
+
+    #[test]
+    fn test_gif_scale_must_fit_its_header() {
+        assert!(VideoRecorder::new(Vec::new(), VideoFormat::Gif, &COLORS, 511).is_ok());
+        let err = VideoRecorder::new(Vec::new(), VideoFormat::Gif, &COLORS, 512)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_y4m_and_rgb_streams() {
+        let mut screen = Screen::new();
+        screen.set_screen(0, 0);
+
+        let mut y4m = VideoRecorder::new(Vec::new(), VideoFormat::Y4m, &COLORS, 2).unwrap();
+        y4m.add_frame(&screen).unwrap();
+        y4m.add_frame(&screen).unwrap();
+        let y4m = y4m.finish().unwrap();
+        let header = b"YUV4MPEG2 W256 H128 F60:1 Ip A1:1 C444\n";
+        assert_eq!(&y4m[..header.len()], header);
+        let frame_len = 6 + 256 * 128 * 3;
+        assert_eq!(y4m.len(), header.len() + 2 * frame_len);
+        let frame = &y4m[header.len()..];
+        assert_eq!(&frame[..6], b"FRAME\n");
+        // White then black in Y, mid grey in U for both
+        assert_eq!(frame[6..6 + 5], [235, 235, 235, 235, 16]);
+        assert_eq!(frame[6 + 256 * 128], 128);
+
+        let mut rgb = VideoRecorder::new(Vec::new(), VideoFormat::Rgb, &COLORS, 1).unwrap();
+        rgb.add_frame(&screen).unwrap();
+        let rgb = rgb.finish().unwrap();
+        assert_eq!(rgb.len(), 128 * 64 * 3);
+        assert_eq!(rgb[..9], [255, 255, 255, 255, 255, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(VideoFormat::from_path("clip.gif"), Some(VideoFormat::Gif));
+        assert_eq!(VideoFormat::from_path("out.Y4M"), Some(VideoFormat::Y4m));
+        assert_eq!(VideoFormat::from_path("frames.raw"), Some(VideoFormat::Rgb));
+        assert_eq!(VideoFormat::from_path("clip.mp4"), None);
+        assert_eq!(VideoFormat::from_path("clip"), None);
+    }
+}
@@ -0,0 +1,174 @@
+// Runs test ROMs headlessly and compares the final screen with the ASCII
+// renders stored in tests/golden. When a change in behavior is intended,
+// re-bless the renders and review the diff:
+//
+//     BLESS=1 cargo test -p chip8-core --test golden
+//
+// The ROMs in tests/roms are this project's own. The public CHIP-8 test
+// suite (https://github.com/Timendus/chip8-test-suite) is GPL-3.0 licensed,
+// so its binaries aren't copied in here and the tests above don't stand in
+// for it. Its test is ignored by default; point CHIP8_TEST_SUITE at the bin
+// directory of a checkout to run corax+, flags, quirks and keypad from it,
+// against the renders in tests/golden/suite (bless them on the first run
+// and check them against another emulator):
+//
+//     CHIP8_TEST_SUITE=~/chip8-test-suite/bin cargo test -p chip8-core --test golden -- --ignored
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8_core::headless::{Finish, Headless};
+use chip8_core::octo::read_rom;
+use chip8_core::{Chip8, Mode, Quirks};
+
+// Enough to finish every test ROM, they all end in an idle loop
+const FRAMES: u64 = 60;
+const INSTRUCTIONS_PER_FRAME: u32 = 100;
+// The suite's quirks test times its display wait over many frames
+const SUITE_FRAMES: u64 = 600;
+// The suite's ROMs skip their menu when this holds a choice
+const SUITE_MENU_CHOICE: usize = 0x1FF;
+
+struct Case {
+    // The golden file is tests/golden/<name>.txt
+    name: String,
+    rom: PathBuf,
+    quirks: Quirks,
+    keys: &'static str,
+    frames: u64,
+    menu_choice: Option<u8>,
+}
+
+impl Case {
+    fn new(name: &str, rom: &str) -> Self {
+        Case {
+            name: name.to_string(),
+            rom: test_dir("roms").join(rom),
+            quirks: Quirks::default(),
+            keys: "",
+            frames: FRAMES,
+            menu_choice: None,
+        }
+    }
+
+    fn suite(name: &str, dir: &Path, rom: &str, menu_choice: Option<u8>) -> Self {
+        Case {
+            name: format!("suite/{}", name),
+            rom: dir.join(rom),
+            frames: SUITE_FRAMES,
+            menu_choice,
+            ..Case::new(name, rom)
+        }
+    }
+}
+
+fn test_dir(dir: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(dir)
+}
+
+fn render(case: &Case) -> String {
+    let rom = read_rom(case.rom.to_str().unwrap()).unwrap();
+    let mut chip8 = Chip8::with_quirks(case.quirks);
+    if case.quirks == Quirks::xochip() {
+        chip8.set_mode(Mode::XoChip);
+    }
+    chip8.load(&rom).unwrap();
+    if let Some(choice) = case.menu_choice {
+        chip8.set_memory_addr(SUITE_MENU_CHOICE, choice).unwrap();
+    }
+
+    let mut headless = Headless::new(chip8, INSTRUCTIONS_PER_FRAME);
+    headless.keys = case.keys.parse().unwrap();
+    let finish = headless.run(case.frames);
+    assert_eq!(
+        finish,
+        Finish::Frames,
+        "{} didn't run to the end",
+        case.name
+    );
+    headless.chip8.screen.to_ascii()
+}
+
+fn check(case: Case) {
+    let actual = render(&case);
+    let golden = test_dir("golden").join(format!("{}.txt", case.name));
+    if env::var_os("BLESS").is_some() {
+        fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        fs::write(&golden, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap_or_else(|err| {
+        panic!(
+            "can't read {}: {}, run with BLESS=1 to create it",
+            golden.display(),
+            err
+        )
+    });
+    assert!(
+        expected == actual,
+        "{} doesn't match {}\nexpected:\n{}\nactual:\n{}\nrun with BLESS=1 if the change is intended",
+        case.name,
+        golden.display(),
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn test_opcodes() {
+    check(Case::new("opcodes", "opcodes.8o"));
+}
+
+#[test]
+fn test_flags() {
+    check(Case::new("flags", "flags.8o"));
+}
+
+#[test]
+fn test_quirks() {
+    check(Case::new("quirks-none", "quirks.8o"));
+    for (preset, quirks) in Quirks::presets() {
+        check(Case {
+            quirks,
+            ..Case::new(&format!("quirks-{}", preset), "quirks.8o")
+        });
+    }
+}
+
+#[test]
+fn test_keypad() {
+    check(Case {
+        // A tapped for a frame, then 7 held for ten
+        keys: "5:A 20:7:10",
+        ..Case::new("keypad", "keypad.8o")
+    });
+}
+
+#[test]
+#[ignore = "needs CHIP8_TEST_SUITE pointing at the public test suite's ROMs"]
+fn test_suite() {
+    let dir = env::var_os("CHIP8_TEST_SUITE")
+        .expect("set CHIP8_TEST_SUITE to the bin directory of the public test suite");
+    let dir = PathBuf::from(dir);
+    check(Case::suite("corax", &dir, "3-corax+.ch8", None));
+    check(Case::suite("flags", &dir, "4-flags.ch8", None));
+    // Menu choices: 1 CHIP-8, 2 SUPER-CHIP, 3 XO-CHIP
+    for (name, quirks, choice) in [
+        ("quirks-vip", Quirks::vip(), 1),
+        ("quirks-schip", Quirks::schip(), 2),
+        ("quirks-xochip", Quirks::xochip(), 3),
+    ] {
+        check(Case {
+            quirks,
+            ..Case::suite(name, &dir, "5-quirks.ch8", Some(choice))
+        });
+    }
+    // The Fx0A test: 5 pressed, then released
+    check(Case {
+        keys: "30:5:10",
+        ..Case::suite("keypad", &dir, "6-keypad.ch8", Some(3))
+    });
+}
@@ -1,23 +1,79 @@
-use std::thread::sleep;
-use std::time::Duration;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, sleep};
 
-use sdl2::{event::Event, keyboard::Keycode, pixels::Color, rect::Rect};
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod},
+    video::FullscreenType,
+};
 
-use crate::emulator::{keyboard::map_sdl_key_to_chip8_key, Chip8};
+use chip8_core::debugger::{self, Command, DEBUGGER_HELP};
+use chip8_core::octo;
+use chip8_core::png;
+use chip8_core::random::{self, RngKind};
+use chip8_core::screen::SCHIP_WIDTH;
+use chip8_core::video::{VideoFormat, VideoRecorder};
+use chip8_core::{
+    Chip8, CpuError, Debugger, Movie, Palette, RewindBuffer, Snapshot, StepOutcome, Theme,
+};
 
-use super::config::WindowConfig;
+use super::audio::Audio;
+use super::clock::{FrameClock, FRAMES_PER_SECOND};
+use super::config::{AppConfig, DisplayConfig};
+use super::keymap::KeyBindings;
+use super::movie::MovieState;
+use super::renderer::Renderer;
 
-const CHIP8_WIDTH: usize = 64;
-const CHIP8_HEIGHT: usize = 32;
-const CHIP8_WINDOW_MULTIPLIER: i32 = 10;
+// F1-F8 save to a slot, Shift+F1-F8 load from it
+const SAVE_SLOT_KEYS: [Keycode; 8] = [
+    Keycode::F1,
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F8,
+];
 
 pub struct App {
-    sdl_context: sdl2::Sdl,
-    video_subsystem: sdl2::VideoSubsystem,
     canvas: sdl2::render::Canvas<sdl2::video::Window>,
+    renderer: Renderer,
+    // The window needs drawing even if no frame has run, e.g. after a resize
+    redraw: bool,
     pub event_pump: sdl2::EventPump,
     is_running: bool,
     chip8: Chip8,
+    fault: Option<CpuError>,
+    clock: FrameClock,
+    instructions_per_frame: u32,
+    audio: Option<Audio>,
+    key_bindings: KeyBindings,
+    // What the config asked for, and the theme T has switched to since
+    display: DisplayConfig,
+    theme: Theme,
+    palette: Palette,
+    title: &'static str,
+    rom_path: Option<String>,
+    // What was loaded from rom_path, kept to restart the machine for movies
+    rom: Vec<u8>,
+    rng: RngKind,
+    seed: u64,
+    // Where F10 records and F11 plays back, next to the ROM unless set
+    movie_path: Option<String>,
+    movie: Option<MovieState>,
+    // Where Ctrl+F12 records video, numbered GIFs next to the ROM unless set
+    video_path: Option<String>,
+    video: Option<(VideoRecorder<BufWriter<File>>, String)>,
+    rewind: RewindBuffer,
+    // Backspace is held down
+    rewinding: bool,
+    debugger: Debugger,
+    // Lines typed at the debugger prompt, read on their own thread
+    debug_console: Option<Receiver<String>>,
 }
 
 impl App {
@@ -25,33 +81,79 @@ impl App {
         self.is_running
     }
 
-    pub fn new(config: WindowConfig) -> Result<Self, String> {
+    pub fn new(config: AppConfig) -> Result<Self, String> {
+        let AppConfig {
+            window,
+            emulator,
+            audio,
+            display,
+            keys,
+        } = config;
         let sdl_context = sdl2::init().unwrap();
         let video_subsystem = sdl_context.video().unwrap();
 
-        let window = video_subsystem
-            .window(config.title, config.width, config.height)
-            .position_centered()
-            .build()
-            .unwrap();
+        let mut builder = video_subsystem.window(window.title, window.width, window.height);
+        builder.position_centered().resizable();
+        if window.fullscreen {
+            builder.fullscreen_desktop();
+        }
+        let sdl_window = builder.build().map_err(|err| err.to_string())?;
 
-        let canvas = window.into_canvas().build().unwrap();
+        let canvas = sdl_window
+            .into_canvas()
+            .present_vsync()
+            .build()
+            .map_err(|err| err.to_string())?;
+        let renderer = Renderer::new(&canvas, display.scaling)?;
         let event_pump = sdl_context.event_pump().unwrap();
+        // Not having a sound card shouldn't stop the games from running
+        let audio = match Audio::new(&sdl_context, audio) {
+            Ok(audio) => Some(audio),
+            Err(err) => {
+                eprintln!("Audio disabled: {}", err);
+                None
+            }
+        };
         let is_running = true;
-        let chip = Chip8::new();
+        let mut chip = Chip8::with_quirks(emulator.quirks);
+        chip.set_mode(emulator.mode);
+        let seed = emulator.seed.unwrap_or_else(random::entropy_seed);
+        chip.rng = emulator.rng.with_seed(seed);
 
         Ok(Self {
-            sdl_context,
-            video_subsystem,
             canvas,
+            renderer,
+            redraw: true,
             event_pump,
             is_running,
             chip8: chip,
+            fault: None,
+            clock: FrameClock::new(),
+            instructions_per_frame: emulator.instructions_per_frame,
+            audio,
+            key_bindings: keys,
+            display,
+            theme: display.theme,
+            palette: display.palette,
+            title: window.title,
+            rom_path: None,
+            rom: Vec::new(),
+            rng: emulator.rng,
+            seed,
+            movie_path: None,
+            movie: None,
+            video_path: None,
+            video: None,
+            rewind: RewindBuffer::new((emulator.rewind_seconds * FRAMES_PER_SECOND) as usize),
+            rewinding: false,
+            debugger: Debugger::new(),
+            debug_console: None,
         })
     }
 
     pub fn process_input(&mut self) -> Result<(), String> {
-        for event in self.event_pump.poll_iter() {
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
@@ -59,66 +161,499 @@ impl App {
                     ..
                 } => self.is_running = false,
                 Event::KeyDown {
-                    keycode: Some(keycode),
+                    keycode: Some(Keycode::M),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(audio) = &mut self.audio {
+                        audio.toggle_mute();
+                    }
+                }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                    ..
+                } => self.redraw = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => self.toggle_fullscreen(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::T),
+                    repeat: false,
+                    ..
+                } => self.next_theme(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => self.toggle_pause(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                        self.toggle_video();
+                    } else {
+                        self.save_screenshot(!keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD));
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    repeat: false,
                     ..
                 } => {
-                    if let Some(_) = map_sdl_key_to_chip8_key(keycode) {
-                        self.chip8.keyboard.key_down(keycode);
+                    if matches!(self.movie, Some(MovieState::Recording { .. })) {
+                        self.stop_movie();
+                    } else if let Err(err) = self.start_recording() {
+                        self.show_status(&format!("recording failed: {}", err));
                     }
                 }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => {
+                    if matches!(self.movie, Some(MovieState::Playing { .. })) {
+                        self.stop_movie();
+                    } else if let Err(err) = self.start_playback() {
+                        self.show_status(&format!("playback failed: {}", err));
+                    }
+                }
+                // Going back in time would break a movie's frame by frame record
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => self.rewinding = self.movie.is_none(),
                 Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => self.rewinding = false,
+                Event::KeyDown {
                     keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if SAVE_SLOT_KEYS.contains(&keycode) => {
+                    let slot = SAVE_SLOT_KEYS.iter().position(|&k| k == keycode).unwrap() + 1;
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        self.load_state(slot);
+                    } else {
+                        self.save_state(slot);
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(scancode),
                     ..
                 } => {
-                    if let Some(_) = map_sdl_key_to_chip8_key(keycode) {
-                        self.chip8.keyboard.key_up(keycode);
+                    if let Some(key) = self.key_bindings.lookup(scancode) {
+                        self.chip8.keyboard.key_down(key);
+                    }
+                }
+                Event::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    if let Some(key) = self.key_bindings.lookup(scancode) {
+                        self.chip8.keyboard.key_up(key);
                     }
                 }
                 _ => {}
             }
         }
+        self.process_debug_commands();
         Ok(())
     }
 
-    pub fn render(&mut self) {
-        for x in 0..CHIP8_WIDTH {
-            for y in 0..CHIP8_HEIGHT {
-                if self.chip8.screen.is_set(x, y) {
-                    let r = Rect::new(
-                        x as i32 * CHIP8_WINDOW_MULTIPLIER,
-                        y as i32 * CHIP8_WINDOW_MULTIPLIER,
-                        CHIP8_WINDOW_MULTIPLIER as u32,
-                        CHIP8_WINDOW_MULTIPLIER as u32,
-                    );
-                    self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-                    self.canvas.fill_rect(r).unwrap();
+    // Starts the debugger prompt on the terminal, with the machine paused
+    pub fn enable_debug_console(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        self.debug_console = Some(receiver);
+        self.debugger.pause();
+        println!("{}", DEBUGGER_HELP);
+        println!("{}", debugger::state(&self.chip8));
+        print_prompt();
+    }
+
+    fn process_debug_commands(&mut self) {
+        let Some(console) = &self.debug_console else {
+            return;
+        };
+        let lines: Vec<String> = console.try_iter().collect();
+        for line in lines {
+            if !line.trim().is_empty() {
+                match line.parse::<Command>() {
+                    Ok(command) => match self.debugger.execute(command, &mut self.chip8) {
+                        Ok(output) if output.is_empty() => {}
+                        Ok(output) => println!("{}", output),
+                        Err(err) => self.halt(err),
+                    },
+                    Err(err) => println!("{}", err),
+                }
+            }
+            print_prompt();
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        if self.debugger.is_paused() {
+            self.debugger.resume(&self.chip8);
+            let _ = self.canvas.window_mut().set_title(self.title);
+        } else {
+            self.debugger.pause();
+            self.show_status("paused");
+        }
+    }
+
+    // Recordings already running keep the colors they started with
+    fn next_theme(&mut self) {
+        self.theme = self.theme.next();
+        self.palette = self.display.palette_for(self.theme);
+        self.redraw = true;
+        self.show_status(&format!("theme: {}", self.theme));
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let mode = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        if let Err(err) = window.set_fullscreen(mode) {
+            self.show_status(&format!("fullscreen failed: {}", err));
+        }
+    }
+
+    pub fn render(&mut self) -> Result<(), String> {
+        self.redraw = false;
+        self.renderer
+            .draw(&mut self.canvas, &self.chip8.screen, &self.palette)
+    }
+
+    // Runs every 60 Hz frame that is due. Returns whether any ran or the
+    // window changed, i.e. whether there is something new to render.
+    pub fn update(&mut self) -> bool {
+        let frames = self.clock.frames_due();
+        for _ in 0..frames {
+            if self.rewinding {
+                self.rewind_frame();
+                self.capture_video_frame();
+                continue;
+            }
+            // A faulted machine is halted: keep showing the last frame, stop executing
+            if self.fault.is_some() {
+                break;
+            }
+            if self.debugger.is_paused() {
+                break;
+            }
+            if let Some(movie) = &mut self.movie {
+                if !movie.before_frame(&mut self.chip8) {
+                    self.end_movie();
+                    self.show_status("movie finished");
                 }
             }
+            match self
+                .debugger
+                .run_frame(&mut self.chip8, self.instructions_per_frame)
+            {
+                Ok(StepOutcome::Continue | StepOutcome::WaitingForKey) => {}
+                Ok(StepOutcome::Exit) => self.is_running = false,
+                Err(err) => self.halt(err),
+            }
+            if let Some(reason) = self.debugger.take_stop() {
+                self.show_status(&reason.to_string());
+                println!("{}", debugger::state(&self.chip8));
+                print_prompt();
+            }
+            self.rewind.push(&self.chip8);
+            self.capture_video_frame();
+        }
+        if let Some(audio) = &mut self.audio {
+            audio.update(&self.chip8, self.fault.is_none());
         }
-        self.canvas.present();
+        frames > 0 || self.redraw
+    }
+
+    // Frame pacing for when vsync isn't available. Only the host loop waits
+    // here, never the emulation itself.
+    pub fn wait_for_next_frame(&self) {
+        sleep(self.clock.time_until_next_frame());
+    }
+
+    // Steps back one recorded frame, playing the game backwards while the key is held
+    fn rewind_frame(&mut self) {
+        // The recorded keypad state is history, what matters is what's held now
+        let held = self.chip8.keyboard.mask();
+        if !self.rewind.rewind(&mut self.chip8) {
+            return;
+        }
+        self.chip8.keyboard.set_mask(held);
+        // Going back to before a fault is how you get out of it
+        self.clear_fault();
+    }
+
+    fn clear_fault(&mut self) {
+        if self.fault.take().is_some() {
+            let _ = self.canvas.window_mut().set_title(self.title);
+        }
+    }
 
-        // Delay timer
-        if self.chip8.delay_timer > 0 {
-            sleep(Duration::from_millis(1));
-            self.chip8.delay_timer -= 1;
+    // Reports something to the user in the window title and on stderr
+    fn show_status(&mut self, message: &str) {
+        eprintln!("{}", message);
+        let title = format!("{} - {}", self.title, message);
+        // Only fails if the title contains a NUL byte, which ours never does
+        let _ = self.canvas.window_mut().set_title(&title);
+    }
+
+    fn halt(&mut self, err: CpuError) {
+        self.show_status(&format!("halted: {}", err));
+        self.fault = Some(err);
+    }
+
+    // Save states live next to the ROM: game.ch8 -> game.ch8.state1
+    fn slot_path(&self, slot: usize) -> Option<String> {
+        self.rom_path
+            .as_ref()
+            .map(|rom| format!("{}.state{}", rom, slot))
+    }
+
+    fn save_state(&mut self, slot: usize) {
+        let Some(path) = self.slot_path(slot) else {
+            return;
+        };
+        let message = match std::fs::write(&path, self.chip8.snapshot().to_bytes()) {
+            Ok(()) => format!("saved state to slot {}", slot),
+            Err(err) => format!("saving slot {} failed: {}", slot, err),
+        };
+        self.show_status(&message);
+    }
+
+    // Writes the screen to the first free game.ch8.shotN.png, at the window's
+    // scale or at the native resolution
+    fn save_screenshot(&mut self, scaled: bool) {
+        let Some(rom) = &self.rom_path else {
+            return;
+        };
+        let path = (1..)
+            .map(|n| format!("{}.shot{}.png", rom, n))
+            .find(|path| !Path::new(path).exists())
+            .unwrap();
+        let screen = &self.chip8.screen;
+        let scale = if scaled {
+            let (window_width, window_height) = self.canvas.output_size().unwrap();
+            (window_width / screen.width() as u32)
+                .min(window_height / screen.height() as u32)
+                .max(1)
+        } else {
+            1
+        };
+        let data = png::screenshot(screen, &self.palette, scale);
+        let message = match std::fs::write(&path, data) {
+            Ok(()) => format!("saved screenshot to {}", path),
+            Err(err) => format!("saving {} failed: {}", path, err),
+        };
+        self.show_status(&message);
+    }
+
+    pub fn set_video_path(&mut self, path: &str) -> Result<(), String> {
+        VideoFormat::from_path(path).ok_or(format!("{} isn't a .gif, .y4m or .rgb file", path))?;
+        self.video_path = Some(path.to_string());
+        Ok(())
+    }
+
+    pub fn toggle_video(&mut self) {
+        if self.video.is_some() {
+            self.stop_video();
+            return;
+        }
+        let path = match &self.video_path {
+            Some(path) => path.clone(),
+            None => {
+                let Some(rom) = &self.rom_path else {
+                    return;
+                };
+                (1..)
+                    .map(|n| format!("{}.clip{}.gif", rom, n))
+                    .find(|path| !Path::new(path).exists())
+                    .unwrap()
+            }
+        };
+        // The same scale as the window, for a 128x64 frame
+        let (window_width, _) = self.canvas.output_size().unwrap();
+        let scale = (window_width / SCHIP_WIDTH as u32).max(1);
+        let format = VideoFormat::from_path(&path).unwrap_or(VideoFormat::Gif);
+        let recorder = File::create(&path).and_then(|file| {
+            VideoRecorder::new(BufWriter::new(file), format, &self.palette, scale)
+        });
+        match recorder {
+            Ok(recorder) => {
+                self.show_status(&format!("recording video to {}", path));
+                self.video = Some((recorder, path));
+            }
+            Err(err) => self.show_status(&format!("recording {} failed: {}", path, err)),
         }
+    }
+
+    // Finishes a video being recorded
+    pub fn stop_video(&mut self) {
+        let Some((recorder, path)) = self.video.take() else {
+            return;
+        };
+        let message = match recorder.finish() {
+            Ok(_) => format!("saved video to {}", path),
+            Err(err) => format!("saving {} failed: {}", path, err),
+        };
+        self.show_status(&message);
+    }
 
-        // Sound timer
-        if self.chip8.sound_timer > 0 {
-            sleep(Duration::from_millis(10 * self.chip8.sound_timer as u64));
-            self.chip8.sound_timer = 0;
+    fn capture_video_frame(&mut self) {
+        let Some((recorder, path)) = &mut self.video else {
+            return;
+        };
+        if let Err(err) = recorder.add_frame(&self.chip8.screen) {
+            let message = format!("recording {} failed: {}", path, err);
+            self.video = None;
+            self.show_status(&message);
         }
+    }
 
-        let opcode = self
-            .chip8
-            .memory_get_short(self.chip8.program_counter as usize);
-        self.chip8.program_counter += 2;
-        self.chip8.exec(opcode);
+    fn load_state(&mut self, slot: usize) {
+        let Some(path) = self.slot_path(slot) else {
+            return;
+        };
+        if self.movie.is_some() {
+            self.show_status("stop the movie before loading a state");
+            return;
+        }
+        let result = std::fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| Snapshot::from_bytes(&data).map_err(|err| err.to_string()));
+        match result {
+            Ok(snapshot) => {
+                // Like rewinding, keys held when saving aren't held now
+                let held = self.chip8.keyboard.mask();
+                self.chip8.restore(&snapshot);
+                self.chip8.keyboard.set_mask(held);
+                // Loading a state is a way out of a fault
+                self.clear_fault();
+                self.show_status(&format!("loaded state from slot {}", slot));
+            }
+            Err(err) => self.show_status(&format!("loading slot {} failed: {}", slot, err)),
+        }
     }
 
     pub fn load_rom(&mut self, filename: &str) -> Result<(), String> {
-        let rom_data = std::fs::read(filename).map_err(|err| err.to_string())?;
+        let rom_data = octo::read_rom(filename)?;
         self.chip8.load(&rom_data).map_err(|err| err.to_string())?;
+        self.rom_path = Some(filename.to_string());
+        self.rom = rom_data;
+        self.rewind.clear();
+        self.rewind.push(&self.chip8);
+        Ok(())
+    }
+
+    pub fn set_movie_path(&mut self, path: &str) {
+        self.movie_path = Some(path.to_string());
+    }
+
+    // Movies live next to the ROM by default: game.ch8 -> game.ch8.movie
+    fn movie_path(&self) -> Option<String> {
+        self.movie_path
+            .clone()
+            .or_else(|| self.rom_path.as_ref().map(|rom| format!("{}.movie", rom)))
+    }
+
+    // Movies start from power-on, so the machine is restarted first
+    fn restart(&mut self, chip8: Chip8) {
+        self.chip8 = chip8;
+        self.clear_fault();
+        self.rewind.clear();
+        self.rewind.push(&self.chip8);
+    }
+
+    pub fn start_recording(&mut self) -> Result<(), String> {
+        let path = self.movie_path().ok_or("no ROM loaded")?;
+        self.stop_movie();
+        let movie = Movie::new(
+            &self.rom,
+            self.chip8.mode(),
+            self.chip8.quirks,
+            self.rng,
+            self.seed,
+            self.instructions_per_frame,
+        );
+        self.restart(movie.machine(&self.rom).map_err(|err| err.to_string())?);
+        self.movie = Some(MovieState::Recording { movie, path });
+        self.show_status("recording");
+        Ok(())
+    }
+
+    pub fn start_playback(&mut self) -> Result<(), String> {
+        let path = self.movie_path().ok_or("no ROM loaded")?;
+        let data = std::fs::read(&path).map_err(|err| format!("{}: {}", path, err))?;
+        let movie = Movie::from_bytes(&data).map_err(|err| format!("{}: {}", path, err))?;
+        let chip8 = movie
+            .machine(&self.rom)
+            .map_err(|err| format!("{}: {}", path, err))?;
+        self.stop_movie();
+        self.restart(chip8);
+        let instructions_per_frame = std::mem::replace(
+            &mut self.instructions_per_frame,
+            movie.instructions_per_frame,
+        );
+        self.movie = Some(MovieState::Playing {
+            movie,
+            frame: 0,
+            instructions_per_frame,
+        });
+        self.show_status("playing movie");
         Ok(())
     }
+
+    // Ends recording or playback, writing out a recording
+    pub fn stop_movie(&mut self) {
+        let message = match self.end_movie() {
+            None => return,
+            Some(MovieState::Playing { .. }) => "movie stopped".to_string(),
+            Some(MovieState::Recording { movie, path }) => {
+                match std::fs::write(&path, movie.to_bytes()) {
+                    Ok(()) => format!("saved {} frames to {}", movie.len(), path),
+                    Err(err) => format!("saving {} failed: {}", path, err),
+                }
+            }
+        };
+        self.show_status(&message);
+    }
+
+    // Takes the movie away, putting back the speed a playback replaced
+    fn end_movie(&mut self) -> Option<MovieState> {
+        let movie = self.movie.take();
+        if let Some(MovieState::Playing {
+            instructions_per_frame,
+            ..
+        }) = movie
+        {
+            self.instructions_per_frame = instructions_per_frame;
+        }
+        movie
+    }
+}
+
+fn print_prompt() {
+    print!("(chip8) ");
+    // The prompt is a nicety, a closed stdout isn't worth stopping for
+    let _ = io::stdout().flush();
 }
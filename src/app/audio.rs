@@ -0,0 +1,70 @@
+use chip8_core::mode::Mode;
+use chip8_core::{Chip8, ToneGenerator};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use super::config::AudioConfig;
+
+const SAMPLE_RATE: i32 = 44100;
+const BUFFER_SAMPLES: u16 = 512;
+
+struct Beeper {
+    tone: ToneGenerator,
+}
+
+impl AudioCallback for Beeper {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.tone.fill(out);
+    }
+}
+
+// The SDL side of the beeper. The device runs continuously and the tone is
+// switched on and off from the emulator's sound timer once per frame.
+pub struct Audio {
+    device: AudioDevice<Beeper>,
+    muted: bool,
+}
+
+impl Audio {
+    pub fn new(sdl_context: &sdl2::Sdl, config: AudioConfig) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: Some(BUFFER_SAMPLES),
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| Beeper {
+            tone: ToneGenerator::new(
+                spec.freq as u32,
+                config.waveform,
+                config.frequency,
+                config.volume,
+            ),
+        })?;
+        device.resume();
+
+        Ok(Audio {
+            device,
+            muted: false,
+        })
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    // `running` is false while the machine is halted, which silences it
+    pub fn update(&mut self, chip8: &Chip8, running: bool) {
+        let playing = running && chip8.sound_timer > 0 && !self.muted;
+        let pattern = match chip8.mode() {
+            Mode::XoChip => Some((chip8.audio_pattern, chip8.pitch)),
+            Mode::Chip8 => None,
+        };
+
+        let mut beeper = self.device.lock();
+        beeper.tone.set_pattern(pattern);
+        beeper.tone.set_playing(playing);
+    }
+}
@@ -0,0 +1,45 @@
+use std::time::{Duration, Instant};
+
+pub const FRAMES_PER_SECOND: u32 = 60;
+// After a long stall (window dragged, machine suspended) drop the backlog
+// instead of fast-forwarding through it
+const MAX_CATCH_UP_FRAMES: u32 = 5;
+
+// Fixed 60 Hz timestep, independent of how fast the host loop spins
+pub struct FrameClock {
+    frame_duration: Duration,
+    last: Instant,
+    accumulator: Duration,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        FrameClock {
+            frame_duration: Duration::from_secs(1) / FRAMES_PER_SECOND,
+            last: Instant::now(),
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    // How many emulated frames are due since the last call
+    pub fn frames_due(&mut self) -> u32 {
+        let now = Instant::now();
+        self.accumulator += now - self.last;
+        self.last = now;
+
+        let mut frames = 0;
+        while self.accumulator >= self.frame_duration {
+            self.accumulator -= self.frame_duration;
+            frames += 1;
+        }
+        if frames > MAX_CATCH_UP_FRAMES {
+            frames = MAX_CATCH_UP_FRAMES;
+        }
+        frames
+    }
+
+    pub fn time_until_next_frame(&self) -> Duration {
+        let elapsed = self.accumulator + self.last.elapsed();
+        self.frame_duration.saturating_sub(elapsed)
+    }
+}
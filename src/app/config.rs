@@ -1,5 +1,14 @@
-const CHIP8_WIDTH: u32 = 64;
-const CHIP8_HEIGHT: u32 = 32;
+use std::fmt;
+use std::str::FromStr;
+
+use chip8_core::palette::parse_color;
+use chip8_core::random::RngKind;
+use chip8_core::screen::{CHIP8_HEIGHT, CHIP8_WIDTH};
+use chip8_core::{Mode, Palette, Quirks, Theme, Waveform};
+
+use super::config_file::ConfigFile;
+use super::keymap::KeyBindings;
+
 const CHIP8_WINDOW_MULTIPLIER: u32 = 10;
 
 #[derive(Debug, Clone, Copy)]
@@ -7,22 +16,194 @@ pub struct WindowConfig {
     pub title: &'static str,
     pub width: u32,
     pub height: u32,
+    pub fullscreen: bool,
 }
 
-impl WindowConfig {
-    pub fn new(title: &'static str, width: u32, height: u32) -> Self {
+impl Default for WindowConfig {
+    fn default() -> Self {
         WindowConfig {
-            title,
-            width,
-            height,
+            title: "CHIP-8 Emulator",
+            width: CHIP8_WIDTH as u32 * CHIP8_WINDOW_MULTIPLIER,
+            height: CHIP8_HEIGHT as u32 * CHIP8_WINDOW_MULTIPLIER,
+            fullscreen: false,
         }
     }
+}
 
-    pub fn default() -> Self {
-        WindowConfig {
-            title: "CHIP-8 Emulator",
-            width: CHIP8_WIDTH * CHIP8_WINDOW_MULTIPLIER,
-            height: CHIP8_HEIGHT * CHIP8_WINDOW_MULTIPLIER,
+// Roughly 660 instructions per second, a comfortable speed for most ROMs
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 11;
+// How far back the rewind key can go
+pub const DEFAULT_REWIND_SECONDS: u32 = 180;
+
+#[derive(Debug, Clone, Copy)]
+pub struct EmulatorConfig {
+    pub quirks: Quirks,
+    pub mode: Mode,
+    pub instructions_per_frame: u32,
+    pub rewind_seconds: u32,
+    pub rng: RngKind,
+    // None picks one at random
+    pub seed: Option<u64>,
+}
+
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        EmulatorConfig {
+            quirks: Quirks::default(),
+            mode: Mode::default(),
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            rewind_seconds: DEFAULT_REWIND_SECONDS,
+            rng: RngKind::default(),
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AudioConfig {
+    pub frequency: f32,
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+// How the screen is fitted to the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scaling {
+    // As large as fits with square pixels, bars fill the rest
+    #[default]
+    Aspect,
+    // Whole multiples of the screen size only, so every pixel is the same size
+    Integer,
+    // The whole window, pixels stretched as needed
+    Stretch,
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "aspect" => Ok(Scaling::Aspect),
+            "integer" => Ok(Scaling::Integer),
+            "stretch" => Ok(Scaling::Stretch),
+            _ => Err(format!(
+                "Unknown scaling '{}', expected aspect, integer or stretch",
+                s
+            )),
         }
     }
 }
+
+impl fmt::Display for Scaling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scaling::Aspect => write!(f, "aspect"),
+            Scaling::Integer => write!(f, "integer"),
+            Scaling::Stretch => write!(f, "stretch"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayConfig {
+    pub theme: Theme,
+    // The theme's colors, with any the config file sets on top
+    pub palette: Palette,
+    pub scaling: Scaling,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            theme: Theme::default(),
+            palette: Theme::default().colors(),
+            scaling: Scaling::default(),
+        }
+    }
+}
+
+// Config file names for the palette entries, in pixel value order
+const COLOR_NAMES: [&str; 4] = ["background", "foreground", "plane2", "both"];
+
+impl DisplayConfig {
+    // Starts with `theme` and its own colors, dropping any set before
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.palette = theme.colors();
+    }
+
+    // The colors to use when switching to `theme`: the configured ones for
+    // the configured theme, the theme's own otherwise
+    pub fn palette_for(&self, theme: Theme) -> Palette {
+        if theme == self.theme {
+            self.palette
+        } else {
+            theme.colors()
+        }
+    }
+
+    // Applies the [display] section. `theme` picks the starting theme and
+    // the color entries override single colors of it, wherever they appear
+    // in the section:
+    //
+    //   [display]
+    //   theme = amber
+    //   background = #000000
+    //   scaling = integer
+    pub fn apply_config(&mut self, config: &ConfigFile) -> Result<(), String> {
+        let mut colors = Vec::new();
+        for entry in config.section("display") {
+            let key = entry.key.to_ascii_lowercase();
+            if key == "theme" {
+                let theme: Theme = entry
+                    .value
+                    .parse()
+                    .map_err(|err| format!("line {}: {}", entry.line, err))?;
+                self.set_theme(theme);
+                continue;
+            }
+            if key == "scaling" {
+                self.scaling = entry
+                    .value
+                    .parse()
+                    .map_err(|err| format!("line {}: {}", entry.line, err))?;
+                continue;
+            }
+            let index = COLOR_NAMES
+                .iter()
+                .position(|&name| name == key)
+                .ok_or(format!(
+                    "line {}: unknown display setting '{}', expected theme, scaling, {}",
+                    entry.line,
+                    entry.key,
+                    COLOR_NAMES.join(", ")
+                ))?;
+            let color =
+                parse_color(&entry.value).map_err(|err| format!("line {}: {}", entry.line, err))?;
+            colors.push((index, color));
+        }
+        for (index, color) in colors {
+            self.palette[index] = color;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AppConfig {
+    pub window: WindowConfig,
+    pub emulator: EmulatorConfig,
+    pub audio: AudioConfig,
+    pub display: DisplayConfig,
+    pub keys: KeyBindings,
+}
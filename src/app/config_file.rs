@@ -0,0 +1,63 @@
+use std::fs;
+
+// A small INI style configuration file:
+//
+//   # comment
+//   [keys]
+//   5 = W
+//
+// Entries are kept in file order with their line numbers, so every section
+// can be interpreted by the part of the app that owns it.
+pub struct Entry {
+    pub section: String,
+    pub key: String,
+    pub value: String,
+    pub line: usize,
+}
+
+pub struct ConfigFile {
+    entries: Vec<Entry>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        ConfigFile::parse(&text).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        let mut section = String::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_ascii_lowercase();
+                continue;
+            }
+
+            match line.split_once('=') {
+                Some((key, value)) => entries.push(Entry {
+                    section: section.clone(),
+                    key: key.trim().to_string(),
+                    value: value.trim().to_string(),
+                    line: line_number,
+                }),
+                None => return Err(format!("line {}: expected 'key = value'", line_number)),
+            }
+        }
+
+        Ok(ConfigFile { entries })
+    }
+
+    pub fn section<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Entry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.section == name)
+    }
+}
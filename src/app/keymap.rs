@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use chip8_core::Chip8Key;
+use sdl2::keyboard::Scancode;
+
+use super::config_file::ConfigFile;
+
+// Bindings are by scancode, i.e. by physical key position, so the default
+// layout lands on the same keys whatever the host keyboard layout is:
+//
+//   1 2 3 C      1 2 3 4
+//   4 5 6 D  ->  Q W E R
+//   7 8 9 E      A S D F
+//   A 0 B F      Z X C V
+const DEFAULT_LAYOUT: [(Scancode, Chip8Key); 16] = [
+    (Scancode::Num1, Chip8Key::Key1),
+    (Scancode::Num2, Chip8Key::Key2),
+    (Scancode::Num3, Chip8Key::Key3),
+    (Scancode::Num4, Chip8Key::KeyC),
+    (Scancode::Q, Chip8Key::Key4),
+    (Scancode::W, Chip8Key::Key5),
+    (Scancode::E, Chip8Key::Key6),
+    (Scancode::R, Chip8Key::KeyD),
+    (Scancode::A, Chip8Key::Key7),
+    (Scancode::S, Chip8Key::Key8),
+    (Scancode::D, Chip8Key::Key9),
+    (Scancode::F, Chip8Key::KeyE),
+    (Scancode::Z, Chip8Key::KeyA),
+    (Scancode::X, Chip8Key::Key0),
+    (Scancode::C, Chip8Key::KeyB),
+    (Scancode::V, Chip8Key::KeyF),
+];
+
+// Taken by the emulator's own hotkeys, which are checked before the keypad
+const HOTKEYS: [Scancode; 16] = [
+    Scancode::Escape,
+    Scancode::M,
+    Scancode::T,
+    Scancode::Backspace,
+    Scancode::F1,
+    Scancode::F2,
+    Scancode::F3,
+    Scancode::F4,
+    Scancode::F5,
+    Scancode::F6,
+    Scancode::F7,
+    Scancode::F8,
+    Scancode::F9,
+    Scancode::F10,
+    Scancode::F11,
+    Scancode::F12,
+];
+
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    keys: HashMap<Scancode, Chip8Key>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            keys: DEFAULT_LAYOUT.into_iter().collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn lookup(&self, scancode: Scancode) -> Option<Chip8Key> {
+        self.keys.get(&scancode).copied()
+    }
+
+    // Replaces every host key bound to `key` with `scancodes`
+    pub fn bind(&mut self, key: Chip8Key, scancodes: &[Scancode]) {
+        self.keys.retain(|_, bound| *bound != key);
+        for &scancode in scancodes {
+            self.keys.insert(scancode, key);
+        }
+    }
+
+    // Applies the [keys] section, one CHIP-8 hex key per line followed by
+    // one or more comma separated SDL scancode names: `5 = W, Up`
+    pub fn apply_config(&mut self, config: &ConfigFile) -> Result<(), String> {
+        for entry in config.section("keys") {
+            let key = u8::from_str_radix(&entry.key, 16)
+                .ok()
+                .and_then(Chip8Key::from_value)
+                .ok_or(format!(
+                    "line {}: '{}' is not a CHIP-8 key, expected 0-F",
+                    entry.line, entry.key
+                ))?;
+
+            let mut scancodes = Vec::new();
+            for name in entry.value.split(',').map(str::trim) {
+                let scancode = Scancode::from_name(name)
+                    .ok_or(format!("line {}: unknown key name '{}'", entry.line, name))?;
+                if HOTKEYS.contains(&scancode) {
+                    return Err(format!(
+                        "line {}: '{}' is an emulator hotkey and can't be bound",
+                        entry.line, name
+                    ));
+                }
+                scancodes.push(scancode);
+            }
+            self.bind(key, &scancodes);
+        }
+        Ok(())
+    }
+}
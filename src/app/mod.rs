@@ -1,3 +1,9 @@
+#[allow(clippy::module_inception)]
 pub mod app;
+pub mod audio;
+pub mod clock;
 pub mod config;
-
+pub mod config_file;
+pub mod keymap;
+pub mod movie;
+pub mod renderer;
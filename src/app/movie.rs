@@ -0,0 +1,33 @@
+use chip8_core::{Chip8, Movie};
+
+// A movie being recorded to or played back from `path`, one keypad state per
+// emulated frame. A playback runs at the movie's speed and remembers the one
+// to go back to when it ends.
+pub enum MovieState {
+    Recording {
+        movie: Movie,
+        path: String,
+    },
+    Playing {
+        movie: Movie,
+        frame: usize,
+        instructions_per_frame: u32,
+    },
+}
+
+impl MovieState {
+    // Records the keypad for the frame about to run, or sets it from the
+    // movie. Returns false once a playback has run out of frames.
+    pub fn before_frame(&mut self, chip8: &mut Chip8) -> bool {
+        match self {
+            MovieState::Recording { movie, .. } => {
+                movie.record(&chip8.keyboard);
+                true
+            }
+            MovieState::Playing { movie, frame, .. } => {
+                *frame += 1;
+                movie.play(*frame - 1, chip8)
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use chip8_core::screen::{SCHIP_HEIGHT, SCHIP_WIDTH};
+use chip8_core::{Palette, Screen};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use super::config::Scaling;
+
+// Draws the screen through a streaming texture as large as the high
+// resolution. A low resolution frame only fills its top left corner, and the
+// GPU scales whichever part is in use up to the window.
+pub struct Renderer {
+    texture: Texture,
+    scaling: Scaling,
+}
+
+impl Renderer {
+    pub fn new(canvas: &Canvas<Window>, scaling: Scaling) -> Result<Self, String> {
+        let texture = canvas
+            .texture_creator()
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                SCHIP_WIDTH as u32,
+                SCHIP_HEIGHT as u32,
+            )
+            .map_err(|err| err.to_string())?;
+        Ok(Renderer { texture, scaling })
+    }
+
+    // Redraws the whole window, background included
+    pub fn draw(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        screen: &Screen,
+        palette: &Palette,
+    ) -> Result<(), String> {
+        let source = Rect::new(0, 0, screen.width() as u32, screen.height() as u32);
+        self.texture
+            .update(source, &screen.to_rgb(palette), screen.width() * 3)
+            .map_err(|err| err.to_string())?;
+
+        let [red, green, blue] = palette[0];
+        canvas.set_draw_color(Color::RGB(red, green, blue));
+        canvas.clear();
+        let destination = viewport(self.scaling, canvas.output_size()?);
+        canvas.copy(&self.texture, source, destination)?;
+        canvas.present();
+        Ok(())
+    }
+}
+
+// Where the screen goes in a window of the given size, centered. Worked out
+// for 128x64 whatever the resolution, both have the same shape and this way
+// a program switching between them doesn't make the picture jump.
+fn viewport(scaling: Scaling, (width, height): (u32, u32)) -> Rect {
+    let (screen_width, screen_height) = (SCHIP_WIDTH as u32, SCHIP_HEIGHT as u32);
+    let (w, h) = match scaling {
+        Scaling::Stretch => (width, height),
+        Scaling::Aspect if width * screen_height > height * screen_width => {
+            (height * screen_width / screen_height, height)
+        }
+        Scaling::Aspect => (width, width * screen_height / screen_width),
+        // Never smaller than 1:1, a tiny window crops instead
+        Scaling::Integer => {
+            let scale = (width / screen_width).min(height / screen_height).max(1);
+            (screen_width * scale, screen_height * scale)
+        }
+    };
+    let x = (width as i32 - w as i32) / 2;
+    let y = (height as i32 - h as i32) / 2;
+    Rect::new(x, y, w, h)
+}
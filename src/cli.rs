@@ -0,0 +1,202 @@
+use chip8_core::random::{parse_seed, RngKind};
+use chip8_core::{Mode, Quirks, Theme};
+
+use crate::app::config::{
+    AudioConfig, Scaling, DEFAULT_INSTRUCTIONS_PER_FRAME, DEFAULT_REWIND_SECONDS,
+};
+
+use crate::app::clock::FRAMES_PER_SECOND;
+
+pub const USAGE: &str = "usage: chip-8-emulator [options] <rom>
+
+options:
+  --quirks <preset>  vip, chip48, schip or xochip
+  --mode <mode>      chip8 or xochip
+  --ipf <n>          instructions executed per 60 Hz frame
+  --hz <n>           CPU clock in instructions per second, instead of --ipf,
+                     a multiple of 60
+  --tone <hz>        beeper frequency
+  --volume <0-100>   beeper volume in percent
+  --waveform <wave>  square, sine or triangle
+  --rewind <seconds> how much history Backspace can rewind through
+  --seed <n>         seed for the random number generator, for repeatable runs
+  --rng <kind>       splitmix, the only generator for now
+  --theme <name>     default, green, amber, lcd or octo colors
+  --scaling <mode>   aspect (default), integer or stretch
+  --fullscreen       start in fullscreen
+  --debug            start paused, with a debugger prompt on the terminal
+  --config <file>    read [keys] bindings and [display] colors from a file
+  --record <file>    record a movie of the session from power-on
+  --play <file>      play a movie back
+  --video <file>     where Ctrl+F12 records video: .gif, .y4m or .rgb
+
+keys:
+  M                  mute or unmute the beeper
+  T                  switch to the next color theme
+  Alt+Enter          toggle fullscreen
+  Backspace          hold to run the game backwards
+  F9                 pause or resume
+  F1-F8              save the machine state to a slot
+  Shift+F1-F8        load the machine state from a slot
+  F10                start or stop recording a movie (game.ch8.movie)
+  F11                play back or stop the movie
+  F12                save a screenshot, Shift+F12 at native resolution
+  Ctrl+F12           start or stop recording video (game.ch8.clip1.gif)";
+
+pub struct Args {
+    pub rom: String,
+    pub quirks: Quirks,
+    pub mode: Mode,
+    pub instructions_per_frame: u32,
+    pub audio: AudioConfig,
+    pub rewind_seconds: u32,
+    pub rng: RngKind,
+    pub seed: Option<u64>,
+    pub theme: Option<Theme>,
+    pub scaling: Option<Scaling>,
+    pub fullscreen: bool,
+    pub debug: bool,
+    pub config: Option<String>,
+    pub movie: Option<MovieArg>,
+    pub video: Option<String>,
+}
+
+pub enum MovieArg {
+    Record(String),
+    Play(String),
+}
+
+fn parse_number(option: &str, value: Option<&String>) -> Result<u32, String> {
+    let value = value.ok_or(format!("{} needs a number", option))?;
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "{} needs a positive number, got '{}'",
+            option, value
+        )),
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut rom = None;
+    let mut quirks = Quirks::default();
+    let mut mode = None;
+    let mut instructions_per_frame = DEFAULT_INSTRUCTIONS_PER_FRAME;
+    let mut audio = AudioConfig::default();
+    let mut rewind_seconds = DEFAULT_REWIND_SECONDS;
+    let mut rng = RngKind::default();
+    let mut seed = None;
+    let mut theme = None;
+    let mut scaling = None;
+    let mut fullscreen = false;
+    let mut debug = false;
+    let mut config = None;
+    let mut movie = None;
+    let mut video = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--quirks" => {
+                let name = iter.next().ok_or("--quirks needs a preset name")?;
+                quirks = name.parse()?;
+            }
+            "--mode" => {
+                let name = iter.next().ok_or("--mode needs a mode name")?;
+                mode = Some(name.parse()?);
+            }
+            "--ipf" => instructions_per_frame = parse_number(arg, iter.next())?,
+            "--hz" => {
+                let hz = parse_number(arg, iter.next())?;
+                // Instructions run in whole frames, so only multiples of the
+                // frame rate can be hit exactly
+                if hz % FRAMES_PER_SECOND != 0 {
+                    let below = hz / FRAMES_PER_SECOND * FRAMES_PER_SECOND;
+                    let above = below + FRAMES_PER_SECOND;
+                    let nearest = if below == 0 {
+                        above.to_string()
+                    } else {
+                        format!("{} or {}", below, above)
+                    };
+                    return Err(format!(
+                        "--hz needs a multiple of {}, the frame rate, try {}",
+                        FRAMES_PER_SECOND, nearest
+                    ));
+                }
+                instructions_per_frame = hz / FRAMES_PER_SECOND;
+            }
+            "--tone" => audio.frequency = parse_number(arg, iter.next())? as f32,
+            "--volume" => {
+                let volume = parse_number(arg, iter.next())?.min(100);
+                audio.volume = volume as f32 / 100.0;
+            }
+            "--waveform" => {
+                let name = iter.next().ok_or("--waveform needs a waveform name")?;
+                audio.waveform = name.parse()?;
+            }
+            "--rewind" => rewind_seconds = parse_number(arg, iter.next())?,
+            "--seed" => {
+                let value = iter.next().ok_or("--seed needs a number")?;
+                seed = Some(parse_seed(value)?);
+            }
+            "--rng" => {
+                let name = iter.next().ok_or("--rng needs a generator name")?;
+                rng = name.parse()?;
+            }
+            "--theme" => {
+                let name = iter.next().ok_or("--theme needs a theme name")?;
+                theme = Some(name.parse()?);
+            }
+            "--scaling" => {
+                let name = iter.next().ok_or("--scaling needs a scaling mode")?;
+                scaling = Some(name.parse()?);
+            }
+            "--fullscreen" => fullscreen = true,
+            "--debug" => debug = true,
+            "--config" => {
+                let path = iter.next().ok_or("--config needs a file name")?;
+                config = Some(path.clone());
+            }
+            "--record" => {
+                let path = iter.next().ok_or("--record needs a file name")?;
+                movie = Some(MovieArg::Record(path.clone()));
+            }
+            "--play" => {
+                let path = iter.next().ok_or("--play needs a file name")?;
+                movie = Some(MovieArg::Play(path.clone()));
+            }
+            "--video" => {
+                let path = iter.next().ok_or("--video needs a file name")?;
+                video = Some(path.clone());
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            _ if rom.is_none() => rom = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let rom = rom.ok_or("You must provide a file to be loaded")?;
+    // The xochip quirks preset implies the XO-CHIP machine unless told otherwise
+    let mode = mode.unwrap_or(if quirks == Quirks::xochip() {
+        Mode::XoChip
+    } else {
+        Mode::Chip8
+    });
+    Ok(Args {
+        rom,
+        quirks,
+        mode,
+        instructions_per_frame,
+        audio,
+        rewind_seconds,
+        rng,
+        seed,
+        theme,
+        scaling,
+        fullscreen,
+        debug,
+        config,
+        movie,
+        video,
+    })
+}
@@ -1,4 +0,0 @@
-pub mod chip;
-pub mod keyboard;
-pub mod screen;
-pub use chip::Chip8;
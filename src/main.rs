@@ -1,25 +1,64 @@
 use std::env;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
 
 use app::app::App;
-use app::config::WindowConfig;
+use app::config::{AppConfig, EmulatorConfig};
+use app::config_file::ConfigFile;
+use chip8_core::random;
 
 mod app;
-mod emulator;
+mod cli;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("You must provide a file to be loaded");
-        std::process::exit(1);
-    }
+    let args = match cli::parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", cli::USAGE);
+            std::process::exit(1);
+        }
+    };
 
-    let filename = &args[1];
+    let filename = &args.rom;
     println!("The filename to load is: {}", filename);
+    println!("Quirks profile: {}, mode: {}", args.quirks, args.mode);
+    // Printed so a run can be repeated with --seed
+    let seed = args.seed.unwrap_or_else(random::entropy_seed);
+    println!("RNG: {}, seed: {:#x}", args.rng, seed);
+
+    let mut config = AppConfig {
+        emulator: EmulatorConfig {
+            quirks: args.quirks,
+            mode: args.mode,
+            instructions_per_frame: args.instructions_per_frame,
+            rewind_seconds: args.rewind_seconds,
+            rng: args.rng,
+            seed: Some(seed),
+        },
+        audio: args.audio,
+        ..AppConfig::default()
+    };
+
+    if let Some(path) = &args.config {
+        let result = ConfigFile::load(path).and_then(|file| {
+            config.keys.apply_config(&file)?;
+            config.display.apply_config(&file)
+        });
+        if let Err(err) = result {
+            eprintln!("Error reading config: {}", err);
+            std::process::exit(1);
+        }
+    }
+    // The command line wins over the config file
+    if let Some(theme) = args.theme {
+        config.display.set_theme(theme);
+    }
+    if let Some(scaling) = args.scaling {
+        config.display.scaling = scaling;
+    }
+    config.window.fullscreen = args.fullscreen;
 
-    let mut app = match App::new(WindowConfig::default()) {
+    let mut app = match App::new(config) {
         Ok(app) => app,
         Err(err) => {
             eprintln!("Error creating App: {}", err);
@@ -32,8 +71,45 @@ fn main() {
         std::process::exit(1);
     }
 
+    if let Some(path) = &args.video {
+        if let Err(err) = app.set_video_path(path) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+
+    let movie = match &args.movie {
+        Some(cli::MovieArg::Record(path)) => {
+            app.set_movie_path(path);
+            app.start_recording()
+        }
+        Some(cli::MovieArg::Play(path)) => {
+            app.set_movie_path(path);
+            app.start_playback()
+        }
+        None => Ok(()),
+    };
+    if let Err(err) = movie {
+        eprintln!("Error starting movie: {}", err);
+        std::process::exit(1);
+    }
+
+    if args.debug {
+        app.enable_debug_console();
+    }
+
     while app.is_running() {
         app.process_input().unwrap();
-        app.render();
+        if app.update() {
+            if let Err(err) = app.render() {
+                eprintln!("Error drawing the screen: {}", err);
+                std::process::exit(1);
+            }
+        } else {
+            app.wait_for_next_frame();
+        }
     }
+    // Saves recordings that are still running
+    app.stop_movie();
+    app.stop_video();
 }